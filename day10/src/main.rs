@@ -1,6 +1,6 @@
-use std::{io::Read, ops::Add};
+use std::{io::Read, ops::Add, fmt};
 
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 struct Point {
     x: isize,
     y: isize,
@@ -24,7 +24,7 @@ impl Point {
 }
 
 // Take map, the width of the map, and a point, and return a reference to the tile in the map a that point
-fn get_tile<'a>(map: &'a [[Point; 2]], pos: Point, width: isize) -> Option<&'a [Point; 2]> {
+fn get_tile(map: &[[Point; 2]], pos: Point, width: isize) -> Option<&[Point; 2]> {
     if pos.y < 0 || pos.x < 0 || pos.x >= width {
         None
     } else {
@@ -37,81 +37,185 @@ fn get_tile<'a>(map: &'a [[Point; 2]], pos: Point, width: isize) -> Option<&'a [
     }
 }
 
-fn find_loop<F: FnMut(&[[Point; 2]], Point, Point, isize)>(input: &str, mut pipe_callback: F) {
-    let width = input.lines().next().unwrap().chars().count() as isize;
+// The offsets to the tiles each type of pipe connects to
+const PIPES: [(char, [Point; 2]); 6] = [
+    ('|', [Point{x: 0, y: -1}, Point{x: 0, y: 1}]),
+    ('-', [Point{x: -1, y: 0}, Point{x: 1, y: 0}]),
+    ('L', [Point{x: 0, y: -1}, Point{x: 1, y: 0}]),
+    ('J', [Point{x: -1, y: 0}, Point{x: 0, y: -1}]),
+    ('7', [Point{x: -1, y: 0}, Point{x: 0, y: 1}]),
+    ('F', [Point{x: 1, y: 0}, Point{x: 0, y: 1}]),
+];
+
+const NO_PIPE: [Point; 2] = [Point{x: 0, y: 0}; 2];
+
+#[derive(Debug)]
+enum StartError {
+    NoStart,
+    NoLoop,
+    Ambiguous(Vec<char>),
+}
 
-    // Build up a map of the area as a flat vector, which each tile containing the offsets to the tiles it's connected
-    // to. Save starting position when we find it.
-    let mut start = Point{x: 0, y: 0};
-    let mut map: Vec<_> = input.chars().enumerate().filter_map(|(n, c)| {
-        match c {
-            '\n' => None,
-            '.' => Some([Point{x: 0, y: 0}; 2]),
-            '|' => Some([Point{x: 0, y: -1}, Point{x: 0, y: 1}]),
-            '-' => Some([Point{x: -1, y: 0}, Point{x: 1, y: 0}]),
-            'L' => Some([Point{x: 0, y: -1}, Point{x: 1, y: 0}]),
-            'J' => Some([Point{x: -1, y: 0}, Point{x: 0, y: -1}]),
-            '7' => Some([Point{x: -1, y: 0}, Point{x: 0, y: 1}]),
-            'F' => Some([Point{x: 1, y: 0}, Point{x: 0, y: 1}]),
-            'S' => {
-                start = Point{x: n as isize % (width + 1), y: n as isize / (width + 1)};
-                Some([Point{x: 0, y: 0}; 2])
-            },
-            _ => panic!(),
+impl fmt::Display for StartError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NoStart => write!(f, "there is no start tile"),
+            Self::NoLoop => write!(f, "no pipe shape for the start tile closes a loop"),
+            Self::Ambiguous(shapes) => {
+                let shapes: Vec<_> = shapes.iter().map(|shape| shape.to_string()).collect();
+                write!(f, "the start tile could be any of {}", shapes.join(", "))
+            }
         }
-    }).collect();
+    }
+}
+
+impl std::error::Error for StartError {}
 
-    // Calculate what directions starting tile connects to, by checking each tile around it to see if it connects back
-    let mut x = 0;
-    for diff in [
-        Point { x: -1, y: 0 },
-        Point { x: 1, y: 0 },
-        Point { x: 0, y: -1 },
-        Point { x: 0, y: 1 },
-    ] {
-        let new_pos = start + diff;
-        if let Some(tile) = get_tile(&map, new_pos, width) {
-            if tile.iter().any(|&diff| new_pos + diff == start) {
-                map[start.index(width)][x] = diff;
-                x += 1;
+struct PipeMap {
+    // A flat vector, with each tile containing the offsets to the tiles it's connected to
+    map: Vec<[Point; 2]>,
+    width: isize,
+    start: Option<Point>,
+}
+
+impl PipeMap {
+    fn parse(input: &str) -> Self {
+        let width = input.lines().next().unwrap().chars().count() as isize;
+
+        // Save starting position when we find it
+        let mut start = None;
+        let map = input.lines().enumerate().flat_map(|(y, line)| line.chars().enumerate().map(move |(x, c)| {
+            (Point { x: x as isize, y: y as isize }, c)
+        })).map(|(pos, c)| {
+            match c {
+                '.' => NO_PIPE,
+                'S' => {
+                    start = Some(pos);
+                    NO_PIPE
+                },
+                _ => PIPES.iter().find(|&&(pipe, _)| pipe == c).unwrap().1,
             }
+        }).collect();
+
+        Self { map, width, start }
+    }
+
+    // Walk from a tile through the pipes, starting in the direction of its first connection, and return the tiles
+    // visited, if we get back to the starting tile through its second connection
+    fn trace_loop(&self, start: Point) -> Option<Vec<Point>> {
+        let (tiles, closed) = self.walk(start, &[]);
+        if closed { Some(tiles) } else { None }
+    }
+
+    // Walk as in trace_loop, returning the tiles visited until the pipes stop connecting both ways, and whether they
+    // closed a loop. None of the tiles can be part of any loop if they don't, and neither can any tile connected both
+    // ways to one of them, so the walk also stops at tiles already walked through from another start.
+    fn walk(&self, start: Point, walked: &[bool]) -> (Vec<Point>, bool) {
+        let start_tile = match get_tile(&self.map, start, self.width) {
+            Some(tile) if *tile != NO_PIPE => tile,
+            _ => return (Vec::new(), false),
+        };
+
+        let mut tiles = vec![start];
+        let mut came_from = start;
+        let mut pos = start + start_tile[0];
+
+        while pos != start {
+            // Check both points current tile connects to. One needs to be the one we came from, and we take the other.
+            let Some(tile) = get_tile(&self.map, pos, self.width) else {
+                return (tiles, false);
+            };
+            let new_pos = match tile.map(|diff| pos + diff) {
+                [a, b] if a == came_from => b,
+                [a, b] if b == came_from => a,
+                _ => return (tiles, false),
+            };
+
+            if tiles.len() > self.map.len() || walked.get(pos.index(self.width)) == Some(&true) {
+                return (tiles, false);
+            }
+            tiles.push(pos);
+            came_from = pos;
+            pos = new_pos;
+        }
+
+        let closed = start + start_tile[1] == came_from;
+        (tiles, closed)
+    }
+
+    // Work out what pipe the starting tile is, by trying each shape that connects to pipes that connect back, and
+    // picking the one that closes a loop
+    fn resolve_start(&mut self) -> Result<char, StartError> {
+        let start = self.start.ok_or(StartError::NoStart)?;
+        let index = start.index(self.width);
+
+        let shapes: Vec<_> = PIPES.iter().filter(|(_, connections)| {
+            self.map[index] = *connections;
+            self.trace_loop(start).is_some()
+        }).collect();
+        self.map[index] = NO_PIPE;
+
+        match shapes[..] {
+            [] => Err(StartError::NoLoop),
+            [&(shape, connections)] => {
+                self.map[index] = connections;
+                Ok(shape)
+            }
+            _ => Err(StartError::Ambiguous(shapes.iter().map(|&&(shape, _)| shape).collect())),
         }
     }
 
-    // Pick one arbitrary direction from start, and walk through the whole loop, calling a callback for each step
-    let mut came_from = start + get_tile(&map, start, width).unwrap()[0];
-    let mut pos = start;
-    loop {
-        pipe_callback(&map, came_from, pos, width);
+    // Find every closed loop of pipes in the map. Loops through the starting tile are found by trying each shape for
+    // it, and come with the shape used.
+    fn find_all_loops(&mut self) -> Vec<(Vec<Point>, Option<char>)> {
+        let start_index = self.start.map(|start| start.index(self.width));
+        if let Some(index) = start_index {
+            self.map[index] = NO_PIPE;
+        }
+
+        let mut visited = vec![false; self.map.len()];
+        let mut loops = Vec::new();
 
-        // Check both points current tile connects to, and take the one we didn't just come from
-        let new_pos = get_tile(&map, pos, width).unwrap().iter().find_map(|&diff| {
-            let new_pos = pos + diff;
-            if new_pos == came_from {
-                None
-            } else {
-                Some(new_pos)
+        for index in 0..self.map.len() {
+            if visited[index] || self.map[index] == NO_PIPE {
+                continue;
             }
-        }).unwrap();
 
-        if new_pos == start {
-            break;
+            let pos = Point { x: index as isize % self.width, y: index as isize / self.width };
+            let (tiles, closed) = self.walk(pos, &visited);
+            for tile in &tiles {
+                visited[tile.index(self.width)] = true;
+            }
+            if closed {
+                loops.push((tiles, None));
+            }
+        }
+
+        if let (Some(start), Some(index)) = (self.start, start_index) {
+            for &(shape, connections) in &PIPES {
+                self.map[index] = connections;
+                if let Some(tiles) = self.trace_loop(start) {
+                    loops.push((tiles, Some(shape)));
+                }
+            }
+            self.map[index] = NO_PIPE;
         }
 
-        came_from = pos;
-        pos = new_pos;
+        loops
     }
 }
 
-fn part_1(input: &str) -> u16 {
-    // Count how many steps we take to get through the loop
-    let mut steps = 0;
-    find_loop(input, |_map, _came_from, _pos, _width| {
-        steps += 1;
-    });
+// Find the loop through the starting tile
+fn find_loop(input: &str) -> (PipeMap, Vec<Point>) {
+    let mut map = PipeMap::parse(input);
+    map.resolve_start().unwrap();
+    let tiles = map.trace_loop(map.start.unwrap()).unwrap();
+    (map, tiles)
+}
 
+fn part_1(input: &str) -> usize {
     // Farthest point is halfway through the loop
-    steps / 2
+    find_loop(input).1.len() / 2
 }
 
 #[derive(Debug, Copy, Clone, PartialEq)]
@@ -120,60 +224,200 @@ enum Tile2 {
     Floor,
 }
 
-fn part_2(input: &str) -> usize {
-    let mut tile_map = Vec::new();
-    let mut map_width = 0;
-    let mut map_height = 0;
+// Get a mask of which tiles are enclosed by a loop, given as the tiles in the order they're walked through
+fn enclosed_tiles(map: &PipeMap, tiles: &[Point]) -> Vec<bool> {
+    let width = map.width as usize;
+    let mut tile_map = vec![Tile2::Floor; map.map.len()];
 
-    find_loop(input, |map, came_from, pos, width| {
-        if tile_map.len() == 0 {
-            tile_map = vec![Tile2::Floor; map.len()];
-            map_width = width as usize;
-            map_height = tile_map.len() / map_width;
-        }
+    for (n, &pos) in tiles.iter().enumerate() {
+        let came_from = tiles[(n + tiles.len() - 1) % tiles.len()];
 
-        if tile_map[pos.y as usize * width as usize + pos.x as usize] == Tile2::Floor {
-            tile_map[pos.y as usize * width as usize + pos.x as usize] = Tile2::Pipe(0);
+        if tile_map[pos.y as usize * width + pos.x as usize] == Tile2::Floor {
+            tile_map[pos.y as usize * width + pos.x as usize] = Tile2::Pipe(0);
         }
-        if tile_map[came_from.y as usize * width as usize + came_from.x as usize] == Tile2::Floor {
-            tile_map[came_from.y as usize * width as usize + came_from.x as usize] = Tile2::Pipe(0);
+        if tile_map[came_from.y as usize * width + came_from.x as usize] == Tile2::Floor {
+            tile_map[came_from.y as usize * width + came_from.x as usize] = Tile2::Pipe(0);
         }
         if pos.y > came_from.y {
-            if let Tile2::Pipe(mov) = &mut tile_map[pos.y as usize * width as usize + pos.x as usize] {
+            if let Tile2::Pipe(mov) = &mut tile_map[pos.y as usize * width + pos.x as usize] {
                 *mov += 1;
             }
-            if let Tile2::Pipe(mov) = &mut tile_map[came_from.y as usize * width as usize + came_from.x as usize] {
+            if let Tile2::Pipe(mov) = &mut tile_map[came_from.y as usize * width + came_from.x as usize] {
                 *mov += 1;
             }
         } else if pos.y < came_from.y {
-            if let Tile2::Pipe(mov) = &mut tile_map[pos.y as usize * width as usize + pos.x as usize] {
+            if let Tile2::Pipe(mov) = &mut tile_map[pos.y as usize * width + pos.x as usize] {
                 *mov -= 1;
             }
-            if let Tile2::Pipe(mov) = &mut tile_map[came_from.y as usize * width as usize + came_from.x as usize] {
+            if let Tile2::Pipe(mov) = &mut tile_map[came_from.y as usize * width + came_from.x as usize] {
                 *mov -= 1;
             }
         }
-    });
+    }
 
-    // for y in
-    (0..map_height).map(|y| {
-        let tile_map = &tile_map;
-        (0..map_width).fold((0, 0), move |(mov_state, count), x| {
+    tile_map.chunks(width).flat_map(|row| {
+        row.iter().scan(0, |mov_state, &tile| {
             // The pipe has two sides, one on the inside and one of the outside. So when we cross the pipe, we go from
             // the inside to the outside or vice versa.
-            match (tile_map[y * map_width + x], mov_state) {
-                (Tile2::Floor, 0) => (mov_state, count),
-                (Tile2::Floor, _) => (mov_state, count + 1),
-                (Tile2::Pipe(mov), _) => (mov_state + mov, count),
+            Some(match tile {
+                Tile2::Floor => *mov_state != 0,
+                Tile2::Pipe(mov) => {
+                    *mov_state += mov;
+                    false
+                }
+            })
+        })
+    }).collect()
+}
+
+// Count the enclosed tiles from the loop vertices instead. The shoelace formula gives the area of the polygon through
+// the centers of the loop tiles, and Pick's theorem, A = I + B/2 - 1, gives the number of tiles inside it from that.
+fn enclosed_by_area(tiles: &[Point]) -> usize {
+    let double_area = tiles.iter().zip(tiles.iter().cycle().skip(1)).map(|(a, b)| {
+        a.x * b.y - b.x * a.y
+    }).sum::<isize>().unsigned_abs();
+
+    (double_area + 2 - tiles.len()) / 2
+}
+
+fn part_2(input: &str) -> usize {
+    let (map, tiles) = find_loop(input);
+    enclosed_tiles(&map, &tiles).into_iter().filter(|&x| x).count()
+}
+
+fn part_2_area(input: &str) -> usize {
+    enclosed_by_area(&find_loop(input).1)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TileClass {
+    Pipe,
+    Inside,
+    Outside,
+}
+
+// Which pipes block the flood fill
+enum Walls<'a> {
+    Loop(&'a [Point]),
+    AllPipes,
+}
+
+// Classify every tile by flood filling from outside the map, at double resolution so that we can squeeze between
+// pipes. Tile (x, y) becomes (2x + 1, 2y + 1), and the tiles in between are blocked where pipes connect through them.
+fn squeeze_classify(map: &PipeMap, walls: &Walls) -> Vec<TileClass> {
+    let width = map.width as usize;
+    let height = map.map.len() / width;
+    let big_width = width * 2 + 1;
+    let mut blocked = vec![false; big_width * (height * 2 + 1)];
+    let mut block = |pos: Point, diff: Point| {
+        blocked[(pos.y * 2 + 1 + diff.y) as usize * big_width + (pos.x * 2 + 1 + diff.x) as usize] = true;
+    };
+
+    match walls {
+        Walls::Loop(tiles) => {
+            for (n, &pos) in tiles.iter().enumerate() {
+                let next = tiles[(n + 1) % tiles.len()];
+                block(pos, Point { x: 0, y: 0 });
+                block(pos, Point { x: next.x - pos.x, y: next.y - pos.y });
             }
-        }).1
-    }).sum()
+        }
+
+        Walls::AllPipes => {
+            for (index, tile) in map.map.iter().enumerate().filter(|&(_, &tile)| tile != NO_PIPE) {
+                let pos = Point { x: (index % width) as isize, y: (index / width) as isize };
+                block(pos, Point { x: 0, y: 0 });
+                // Connections off the edge of the map would wall off the padding the fill starts from
+                for &diff in tile.iter().filter(|&&diff| {
+                    let next = pos + diff;
+                    next.x >= 0 && next.y >= 0 && next.x < width as isize && next.y < height as isize
+                }) {
+                    block(pos, diff);
+                }
+            }
+        }
+    }
+
+    let mut outside = vec![false; blocked.len()];
+    let mut to_visit = vec![0];
+    while let Some(index) = to_visit.pop() {
+        if outside[index] || blocked[index] {
+            continue;
+        }
+        outside[index] = true;
+
+        let (x, y) = (index % big_width, index / big_width);
+        if x > 0 { to_visit.push(index - 1); }
+        if x + 1 < big_width { to_visit.push(index + 1); }
+        if y > 0 { to_visit.push(index - big_width); }
+        if index + big_width < blocked.len() { to_visit.push(index + big_width); }
+    }
+
+    (0..map.map.len()).map(|index| {
+        let big_index = (index / width * 2 + 1) * big_width + index % width * 2 + 1;
+        if blocked[big_index] {
+            TileClass::Pipe
+        } else if outside[big_index] {
+            TileClass::Outside
+        } else {
+            TileClass::Inside
+        }
+    }).collect()
+}
+
+// Draw the map with inside tiles as I and outside tiles as O, keeping the pipes as they are
+fn render_classes(input: &str, classes: &[TileClass]) -> String {
+    let mut classes = classes.iter();
+    input.lines().map(|line| {
+        line.chars().map(|c| match classes.next().unwrap() {
+            TileClass::Pipe => c,
+            TileClass::Inside => 'I',
+            TileClass::Outside => 'O',
+        }).chain(['\n']).collect::<String>()
+    }).collect()
 }
 
 fn main() {
     let mut input = String::new();
     std::io::stdin().read_to_string(&mut input).unwrap();
 
+    let mut map = PipeMap::parse(&input);
+
+    match std::env::args().nth(1).as_deref() {
+        Some("loops") => {
+            for (tiles, start_shape) in map.find_all_loops() {
+                print!("Loop of length {} through ({}, {})", tiles.len(), tiles[0].x, tiles[0].y);
+                match start_shape {
+                    Some(shape) => println!(", with the start tile as {}", shape),
+                    None => println!(),
+                }
+            }
+            return;
+        }
+
+        // Flood fill with every pipe in the map as a wall
+        Some("squeeze-all") => {
+            let _ = map.resolve_start();
+            print!("{}", render_classes(&input, &squeeze_classify(&map, &Walls::AllPipes)));
+            return;
+        }
+
+        // Flood fill with the main loop as a wall
+        Some("squeeze") => {
+            let (map, tiles) = find_loop(&input);
+            let classes = squeeze_classify(&map, &Walls::Loop(&tiles));
+            print!("{}", render_classes(&input, &classes));
+            println!("Inside: {}", classes.iter().filter(|&&class| class == TileClass::Inside).count());
+            return;
+        }
+
+        _ => {}
+    }
+
+    if let Err(err) = map.resolve_start() {
+        eprintln!("Invalid start: {}", err);
+        std::process::exit(1);
+    }
+
     let start_time = std::time::Instant::now();
     let result = part_1(&input);
     println!("Part 1 time: {:?}", std::time::Instant::now() - start_time);
@@ -183,4 +427,135 @@ fn main() {
     let result = part_2(&input);
     println!("Part 2 time: {:?}", std::time::Instant::now() - start_time);
     println!("Part 2 result: {}", result);
+
+    let start_time = std::time::Instant::now();
+    let result = part_2_area(&input);
+    println!("Part 2 (shoelace) time: {:?}", std::time::Instant::now() - start_time);
+    println!("Part 2 (shoelace) result: {}", result);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::{HashMap, HashSet};
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    // Get the boundary of a set of unit squares, with corners at tile centers, as a loop of tiles. Returns None if the
+    // boundary isn't a single simple loop.
+    fn square_boundary(squares: &HashSet<Point>) -> Option<Vec<Point>> {
+        let mut edges: HashMap<Point, Vec<Point>> = HashMap::new();
+        for &square in squares {
+            let corners = [(0, 0), (1, 0), (1, 1), (0, 1)].map(|(x, y)| square + Point { x, y });
+            for n in 0..4 {
+                let neighbor = square + Point { x: [0, 1, 0, -1][n], y: [-1, 0, 1, 0][n] };
+                if !squares.contains(&neighbor) {
+                    let (a, b) = (corners[n], corners[(n + 1) % 4]);
+                    edges.entry(a).or_default().push(b);
+                    edges.entry(b).or_default().push(a);
+                }
+            }
+        }
+
+        if edges.values().any(|connected| connected.len() != 2) {
+            return None;
+        }
+
+        let start = *edges.keys().next()?;
+        let mut tiles = vec![start];
+        let mut came_from = start;
+        let mut pos = edges[&start][0];
+        while pos != start {
+            tiles.push(pos);
+            let next = edges[&pos].iter().copied().find(|&next| next != came_from)?;
+            came_from = pos;
+            pos = next;
+        }
+
+        if tiles.len() == edges.len() { Some(tiles) } else { None }
+    }
+
+    // Generate a map with a random loop, as the boundary of a random set of squares with corners at tile centers,
+    // grown one square at a time as long as the boundary stays a single loop. The rest of the map is filled with
+    // random tiles.
+    fn random_map<R: Rng>(rng: &mut R, width: isize, height: isize, moves: usize) -> String {
+        let mut squares = HashSet::from([Point { x: rng.gen_range(0..width - 1), y: rng.gen_range(0..height - 1) }]);
+        let mut tiles = square_boundary(&squares).unwrap();
+
+        for _ in 0..moves {
+            let &square = squares.iter().nth(rng.gen_range(0..squares.len())).unwrap();
+            let n = rng.gen_range(0..4);
+            let new_square = square + Point { x: [0, 1, 0, -1][n], y: [-1, 0, 1, 0][n] };
+            if new_square.x < 0 || new_square.y < 0 || new_square.x >= width - 1 || new_square.y >= height - 1 {
+                continue;
+            }
+
+            if squares.insert(new_square) {
+                match square_boundary(&squares) {
+                    Some(new_tiles) => { tiles = new_tiles; }
+                    None => { squares.remove(&new_square); }
+                }
+            }
+        }
+
+        let mut map: Vec<Vec<_>> = (0..height).map(|_| (0..width).map(|_| {
+            ['.', '|', '-', 'L', 'J', '7', 'F'][rng.gen_range(0..7)]
+        }).collect()).collect();
+
+        for (n, &pos) in tiles.iter().enumerate() {
+            let mut connections = [
+                tiles[(n + tiles.len() - 1) % tiles.len()] + Point { x: -pos.x, y: -pos.y },
+                tiles[(n + 1) % tiles.len()] + Point { x: -pos.x, y: -pos.y },
+            ];
+            let &(pipe, _) = PIPES.iter().find(|(_, pipe_connections)| {
+                connections.sort_by_key(|diff| pipe_connections.iter().position(|x| x == diff));
+                connections == *pipe_connections
+            }).unwrap();
+            map[pos.y as usize][pos.x as usize] = pipe;
+        }
+
+        let start = tiles[rng.gen_range(0..tiles.len())];
+        map[start.y as usize][start.x as usize] = 'S';
+
+        map.into_iter().map(|row| row.into_iter().chain(['\n']).collect::<String>()).collect()
+    }
+
+    // Generate random maps from fixed seeds, skipping those where random tiles next to the start close another loop
+    // through it
+    fn random_maps(count: u64) -> impl Iterator<Item = (u64, String)> {
+        (0..count).filter_map(|seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let (width, height) = (rng.gen_range(3..30), rng.gen_range(3..30));
+            let moves = rng.gen_range(0..500);
+            let input = random_map(&mut rng, width, height, moves);
+            PipeMap::parse(&input).resolve_start().is_ok().then_some((seed, input))
+        })
+    }
+
+    #[test]
+    fn scan_matches_shoelace() {
+        for (seed, input) in random_maps(300) {
+            assert_eq!(part_2(&input), part_2_area(&input), "seed {}, map:\n{}", seed, input);
+        }
+    }
+
+    #[test]
+    fn scan_matches_flood_fill() {
+        for (seed, input) in random_maps(300) {
+            let (map, tiles) = find_loop(&input);
+            let squeezed = squeeze_classify(&map, &Walls::Loop(&tiles)).into_iter().filter(|&class| {
+                class == TileClass::Inside
+            }).count();
+            assert_eq!(part_2(&input), squeezed, "seed {}, map:\n{}", seed, input);
+        }
+    }
+
+    #[test]
+    fn squeeze_all_ignores_connections_off_map() {
+        for input in ["J....\n.....\n", "..|..\n.....\n..|..\n"] {
+            let mut map = PipeMap::parse(input);
+            let _ = map.resolve_start();
+            let classes = squeeze_classify(&map, &Walls::AllPipes);
+            assert!(!classes.contains(&TileClass::Inside), "map:\n{}", input);
+        }
+    }
 }
@@ -1,6 +1,6 @@
 use std::io::Read;
 
-fn sum_distances(positions: &[(u8, u8)], expansion_factor: u64) -> u64 {
+fn sum_distances(positions: &[(u32, u32)], expansion_factor: u64) -> u64 {
     positions.windows(2).fold((0, 0, 0), |(
         // Sum of distances between each galaxy up to and including prev_pos
         total_distance,
@@ -19,7 +19,7 @@ fn sum_distances(positions: &[(u8, u8)], expansion_factor: u64) -> u64 {
         let galaxies_before_current = galaxies_before_prev + pair[0].1 as u64;
 
         // For each previous galaxy the distance increases equal to the traveled distances
-        let distance_to_current = distance_to_prev + galaxies_before_current * traveled_distance as u64;
+        let distance_to_current = distance_to_prev + galaxies_before_current * traveled_distance;
 
         // For each galaxy at current position, add distances between all previously visited galaxies and current galaxy
         // to total.
@@ -29,34 +29,95 @@ fn sum_distances(positions: &[(u8, u8)], expansion_factor: u64) -> u64 {
     }).0
 }
 
-fn solve(input: &str, expansion_factor: u64) -> u64 {
-    // We're looking for the manhattan distance, and there are no obstacles, so the shortest distance is just the sum of
-    // the distances on x and y axis, so we can sum up x and y separately. x and y positions need to be in order.
-    let mut galaxies_by_column = vec![0; input.lines().count()];
-    let mut ys = Vec::new();
-
-    for (y, line) in input.lines().enumerate() {
-        for (x, c) in line.chars().enumerate() {
-            let mut count = 0;
-            if c == '#' {
-                galaxies_by_column[x] += 1;
-                count += 1;
-            }
-            if count > 0 {
-                ys.push((y as u8, count));
-            }
+#[derive(Debug, Clone, Copy)]
+struct Expansion {
+    rows: u64,
+    columns: u64,
+}
+
+struct Universe {
+    width: u32,
+    height: u32,
+    // Positions as (x, y), in reading order
+    galaxies: Vec<(u32, u32)>,
+}
+
+impl Universe {
+    fn parse(input: &str) -> Self {
+        let galaxies = input.lines().enumerate().flat_map(|(y, line)| {
+            line.chars().enumerate().filter(|&(_, c)| c == '#').map(move |(x, _)| (x as u32, y as u32))
+        }).collect();
+
+        Self {
+            width: input.lines().map(|line| line.chars().count()).max().unwrap_or(0) as u32,
+            height: input.lines().count() as u32,
+            galaxies,
         }
     }
 
-    let xs: Vec<_> = galaxies_by_column.into_iter().enumerate().filter_map(|(x, count)| {
-        if count > 0 {
-            Some((x as u8, count))
-        } else {
-            None
+    // Get the number of galaxies at each position along one axis
+    fn counts(&self, len: u32, axis: fn(&(u32, u32)) -> u32) -> Vec<u32> {
+        let mut counts = vec![0; len as usize];
+        for galaxy in &self.galaxies {
+            counts[axis(galaxy) as usize] += 1;
         }
-    }).collect();
+        counts
+    }
+
+    fn sum_distances(&self, expansion: Expansion) -> u64 {
+        // We're looking for the manhattan distance, and there are no obstacles, so the shortest distance is just the
+        // sum of the distances on x and y axis, so we can sum up x and y separately. x and y positions need to be in
+        // order.
+        let occupied = |counts: Vec<u32>| -> Vec<_> {
+            counts.into_iter().enumerate().filter_map(|(n, count)| {
+                if count > 0 {
+                    Some((n as u32, count))
+                } else {
+                    None
+                }
+            }).collect()
+        };
+        let xs = occupied(self.counts(self.width, |&(x, _)| x));
+        let ys = occupied(self.counts(self.height, |&(_, y)| y));
+
+        sum_distances(&xs, expansion.columns) + sum_distances(&ys, expansion.rows)
+    }
 
-    sum_distances(&xs, expansion_factor) + sum_distances(&ys, expansion_factor)
+    // Get the position of every galaxy after expansion
+    fn expanded(&self, expansion: Expansion) -> Vec<(u64, u64)> {
+        // Position of each line after expansion, from the number of empty lines before it
+        let expand = |counts: Vec<u32>, factor: u64| -> Vec<u64> {
+            counts.into_iter().scan(0, |pos, count| {
+                let current = *pos;
+                *pos += if count == 0 { factor } else { 1 };
+                Some(current)
+            }).collect()
+        };
+        let xs = expand(self.counts(self.width, |&(x, _)| x), expansion.columns);
+        let ys = expand(self.counts(self.height, |&(_, y)| y), expansion.rows);
+
+        self.galaxies.iter().map(|&(x, y)| (xs[x as usize], ys[y as usize])).collect()
+    }
+
+    fn distance(&self, a: usize, b: usize, expansion: Expansion) -> u64 {
+        let positions = self.expanded(expansion);
+        let ((ax, ay), (bx, by)) = (positions[a], positions[b]);
+        ax.abs_diff(bx) + ay.abs_diff(by)
+    }
+
+    // Get the index of and distance to the closest other galaxy, for each galaxy
+    fn nearest_neighbours(&self, expansion: Expansion) -> Vec<Option<(usize, u64)>> {
+        let positions = self.expanded(expansion);
+        positions.iter().enumerate().map(|(n, &(x, y))| {
+            positions.iter().enumerate().filter(|&(other, _)| other != n).map(|(other, &(other_x, other_y))| {
+                (other, x.abs_diff(other_x) + y.abs_diff(other_y))
+            }).min_by_key(|&(_, distance)| distance)
+        }).collect()
+    }
+}
+
+fn solve(input: &str, expansion_factor: u64) -> u64 {
+    Universe::parse(input).sum_distances(Expansion { rows: expansion_factor, columns: expansion_factor })
 }
 
 fn part_1(input: &str) -> u64 {
@@ -71,6 +132,41 @@ fn main() {
     let mut input = String::new();
     std::io::stdin().read_to_string(&mut input).unwrap();
 
+    // Galaxies are numbered from 1 on the command line
+    let mut args = std::env::args().skip(1);
+    if let Some(command) = args.next() {
+        let universe = Universe::parse(&input);
+        let mut args = args.map(|arg| arg.parse::<u64>().unwrap());
+        let expansion = |args: &mut dyn Iterator<Item = u64>| {
+            let rows = args.next().unwrap_or(2);
+            Expansion { rows, columns: args.next().unwrap_or(rows) }
+        };
+
+        match command.as_str() {
+            "sum" => {
+                println!("Sum of distances: {}", universe.sum_distances(expansion(&mut args)));
+            }
+
+            "distance" => {
+                let a = args.next().unwrap() as usize - 1;
+                let b = args.next().unwrap() as usize - 1;
+                println!("Distance: {}", universe.distance(a, b, expansion(&mut args)));
+            }
+
+            "nearest" => {
+                for (n, nearest) in universe.nearest_neighbours(expansion(&mut args)).into_iter().enumerate() {
+                    match nearest {
+                        Some((other, distance)) => println!("{}: {} at distance {}", n + 1, other + 1, distance),
+                        None => println!("{}: no other galaxies", n + 1),
+                    }
+                }
+            }
+
+            _ => panic!("Unknown command {}", command),
+        }
+        return;
+    }
+
     let start_time = std::time::Instant::now();
     let result = part_1(&input);
     println!("Part 1 time: {:?}", std::time::Instant::now() - start_time);
@@ -1,4 +1,6 @@
-use std::io::Read;
+use std::{io::Read, fmt};
+use num::{BigUint, CheckedAdd, One, Zero};
+use rand::Rng;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 enum Spring {
@@ -7,110 +9,483 @@ enum Spring {
     Unknown,
 }
 
-fn arrangements(input: &str, repetitions: usize) -> u64 {
-    let mut springs_single_rep = Vec::new();
-    let mut springs = Vec::<Spring>::new();
-    let mut groups_single_rep = Vec::new();
-    let mut groups = Vec::<usize>::new();
-    let mut stack = Vec::new();
-    let mut cache = Vec::new();
-
-    input.lines().map(|line| {
-        springs_single_rep.clear();
-        springs.clear();
-        groups_single_rep.clear();
-        groups.clear();
-        stack.clear();
-        cache.clear();
-
-        let mut parts = line.split_ascii_whitespace();
-
-        springs_single_rep.extend(parts.next().unwrap().chars().map(|c| match c {
-            '#' => Spring::Broken,
-            '.' => Spring::Working,
-            '?' => Spring::Unknown,
-            _ => panic!(),
-        }));
-
-        groups_single_rep.extend(parts.next().unwrap().split(',').map(|num| num.parse::<usize>().unwrap()));
-
-        springs.reserve((springs_single_rep.len() + 1 * repetitions) - 1);
-        groups.reserve(groups_single_rep.len() * repetitions);
+// A type arrangements can be counted in
+trait Count: Clone + Zero + One + CheckedAdd {}
+
+impl<T: Clone + Zero + One + CheckedAdd> Count for T {}
+
+#[derive(Debug)]
+struct Overflow;
+
+impl fmt::Display for Overflow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "number of arrangements doesn't fit in count type")
+    }
+}
+
+impl std::error::Error for Overflow {}
+
+fn parse_springs(src: &str) -> impl Iterator<Item = Spring> + '_ {
+    src.chars().map(|c| match c {
+        '#' => Spring::Broken,
+        '.' => Spring::Working,
+        '?' => Spring::Unknown,
+        _ => panic!(),
+    })
+}
+
+struct Row {
+    springs: Vec<Spring>,
+    groups: Vec<usize>,
+}
+
+impl Row {
+    // Parse a row, unfolded into the given number of copies. With no copies the row is empty, and has exactly one
+    // arrangement.
+    fn parse(line: &str, repetitions: usize) -> Self {
+        let (springs_src, groups_src) = line.split_once(' ').unwrap();
+        let springs_single_rep: Vec<_> = parse_springs(springs_src).collect();
+        let groups_single_rep: Vec<usize> = groups_src.split(',').map(|num| num.parse().unwrap()).collect();
+
+        let mut springs = Vec::new();
         for _ in 0..repetitions {
-            if springs.len() > 0 {
+            if !springs.is_empty() {
                 springs.push(Spring::Unknown);
             }
             springs.extend(&springs_single_rep);
-            groups.extend(&groups_single_rep);
         }
 
-        cache.resize((groups.len() - 1) * springs.len(), None);
-        stack.reserve(groups.len() - 1);
+        Self { springs, groups: groups_single_rep.repeat(repetitions) }
+    }
 
-        let mut count = 0;
-        let mut pos = 0;
+    // Whether group number group can be placed starting at pos, with a working spring or the end of the row after it
+    fn fits(&self, group: usize, pos: usize) -> bool {
+        let end = pos + self.groups[group];
+        end <= self.springs.len() &&
+            self.springs[pos..end].iter().all(|&x| x != Spring::Working) &&
+            self.springs.get(end) != Some(&Spring::Broken)
+    }
 
-        loop {
-            let mut indent = String::new();
-            for _ in 0..stack.len() {
-                indent.extend("  ".chars());
+    // Build a table of how many ways there are to place the groups from group onward in the springs from pos onward,
+    // indexed by group * (springs.len() + 2) + pos
+    fn count_table<T: Count>(&self) -> Result<Vec<T>, Overflow> {
+        let stride = self.springs.len() + 2;
+        let mut table = vec![T::zero(); (self.groups.len() + 1) * stride];
+
+        // With no groups left, there's one way if there are no broken springs left
+        for pos in (0..stride).rev() {
+            table[self.groups.len() * stride + pos] = if pos >= self.springs.len() {
+                T::one()
+            } else if self.springs[pos] == Spring::Broken {
+                T::zero()
+            } else {
+                table[self.groups.len() * stride + pos + 1].clone()
+            };
+        }
+
+        for group in (0..self.groups.len()).rev() {
+            for pos in (0..self.springs.len()).rev() {
+                let place = if self.fits(group, pos) {
+                    table[(group + 1) * stride + (pos + self.groups[group] + 1).min(self.springs.len())].clone()
+                } else {
+                    T::zero()
+                };
+                let skip = if self.springs[pos] == Spring::Broken {
+                    T::zero()
+                } else {
+                    table[group * stride + pos + 1].clone()
+                };
+                table[group * stride + pos] = place.checked_add(&skip).ok_or(Overflow)?;
+            }
+        }
+
+        Ok(table)
+    }
+
+    // Get arrangement number k, in lexicographic order with # before ., by choosing at each point whether to start the
+    // next group or leave a working spring, depending on whether k is within the number of arrangements starting with
+    // the group
+    fn kth(&self, table: &[u64], mut k: u64) -> Option<Vec<Spring>> {
+        let stride = self.springs.len() + 2;
+        if k >= table[0] {
+            return None;
+        }
+
+        let mut arrangement = Vec::with_capacity(self.springs.len());
+        let (mut group, mut pos) = (0, 0);
+        while pos < self.springs.len() {
+            if group < self.groups.len() && self.fits(group, pos) {
+                let end = pos + self.groups[group];
+                let place = table[(group + 1) * stride + (end + 1).min(self.springs.len())];
+                if k < place {
+                    arrangement.extend(std::iter::repeat_n(Spring::Broken, self.groups[group]));
+                    if end < self.springs.len() {
+                        arrangement.push(Spring::Working);
+                    }
+                    group += 1;
+                    pos = end + 1;
+                    continue;
+                }
+                k -= place;
             }
-            let len = groups[stack.len()];
-            let end = pos + len;
-
-            if end > springs.len() || (pos > 0 && springs[pos - 1] == Spring::Broken) {
-                // There's a broken spring that's not included in a group, or we've gone past the end
-                if let Some((x, y)) = stack.pop() {
-                    pos = x;
-                    cache[stack.len() * springs.len() + pos] = Some(count);
-                    count = y + count;
-                    pos += 1;
+
+            arrangement.push(Spring::Working);
+            pos += 1;
+        }
+
+        Some(arrangement)
+    }
+
+    // Find the condition of each spring that's the same in every arrangement, leaving the rest unknown, or None if
+    // there are no arrangements. Combines the count table for this row, which counts ways to finish from each point,
+    // with the one for the reversed row, which counts ways to get to each point.
+    fn forced<T: Count>(&self) -> Result<Option<Vec<Spring>>, Overflow> {
+        let (len, groups) = (self.springs.len(), self.groups.len());
+        let reversed = Row {
+            springs: self.springs.iter().rev().copied().collect(),
+            groups: self.groups.iter().rev().copied().collect(),
+        };
+        let (after, before) = (self.count_table::<T>()?, reversed.count_table::<T>()?);
+        if after[0].is_zero() {
+            return Ok(None);
+        }
+
+        // Ways to place the groups before group in the springs before pos, and the groups from group onward in the
+        // springs from pos onward
+        let stride = len + 2;
+        let ways_before = |group: usize, pos: usize| &before[(groups - group) * stride + len - pos];
+        let ways_after = |group: usize, pos: usize| &after[group * stride + pos.min(len)];
+
+        let mut can_work = vec![false; len];
+        let mut starts = vec![0i32; len + 1];
+        for pos in 0..len {
+            for group in 0..=groups {
+                if self.springs[pos] != Spring::Broken &&
+                    !ways_before(group, pos).is_zero() && !ways_after(group, pos + 1).is_zero() {
+                    can_work[pos] = true;
+                }
+
+                // Place the group at pos, with a working spring or the start of the row before it
+                if group == groups || !self.fits(group, pos) {
                     continue;
+                }
+                let fits_before = if pos == 0 {
+                    group == 0
                 } else {
-                    break;
+                    self.springs[pos - 1] != Spring::Broken && !ways_before(group, pos - 1).is_zero()
+                };
+                if fits_before && !ways_after(group + 1, pos + self.groups[group] + 1).is_zero() {
+                    starts[pos] += 1;
+                    starts[pos + self.groups[group]] -= 1;
                 }
             }
+        }
+
+        let mut covering = 0;
+        Ok(Some(starts[..len].iter().zip(can_work).map(|(&x, can_work)| {
+            covering += x;
+            match (covering > 0, can_work) {
+                (true, false) => Spring::Broken,
+                (false, true) => Spring::Working,
+                _ => Spring::Unknown,
+            }
+        }).collect()))
+    }
+
+    // Lazily go through every arrangement, in lexicographic order
+    fn arrangements(&self) -> Result<impl Iterator<Item = Vec<Spring>> + '_, Overflow> {
+        let table = self.count_table()?;
+        Ok((0..table[0]).map(move |k| self.kth(&table, k).unwrap()))
+    }
+
+    // Draw a uniformly random arrangement, by picking a random rank
+    fn random<R: Rng>(&self, table: &[u64], rng: &mut R) -> Option<Vec<Spring>> {
+        if table[0] == 0 {
+            None
+        } else {
+            self.kth(table, rng.gen_range(0..table[0]))
+        }
+    }
+}
+
+fn format_springs(springs: &[Spring]) -> String {
+    springs.iter().map(|spring| match spring {
+        Spring::Broken => '#',
+        Spring::Working => '.',
+        Spring::Unknown => '?',
+    }).collect()
+}
+
+fn arrangements<T: Count>(input: &str, repetitions: usize) -> Result<T, Overflow> {
+    input.lines().try_fold(T::zero(), |acc, line| {
+        let count = Row::parse(line, repetitions).count_table::<T>()?.swap_remove(0);
+        acc.checked_add(&count).ok_or(Overflow)
+    })
+}
+
+#[derive(Debug)]
+enum PuzzleError {
+    MissingColumns,
+    BadClue { line: usize },
+}
+
+impl fmt::Display for PuzzleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::MissingColumns => write!(f, "expected row clues and column clues separated by a blank line"),
+            Self::BadClue { line } => write!(f, "line {} isn't a comma separated list of group lengths", line),
+        }
+    }
+}
+
+impl std::error::Error for PuzzleError {}
+
+// A nonogram, where each row and column is a row of springs with unknown condition and known group lengths
+struct Nonogram {
+    rows: Vec<Vec<usize>>,
+    columns: Vec<Vec<usize>>,
+}
 
-            if (end < springs.len() && springs[end] == Spring::Broken) ||
-                springs[pos..end].iter().any(|&x| x == Spring::Working) {
-                // Not a valid position
-                pos += 1;
+impl Nonogram {
+    // Parse one line of clues per row, then a blank line, then one line of clues per column, where each line is a comma
+    // separated list of group lengths, and 0 means a line without any groups
+    fn parse(input: &str) -> Result<Self, PuzzleError> {
+        let mut sections = vec![Vec::new()];
+        for (n, line) in input.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                if !sections.last().unwrap().is_empty() {
+                    sections.push(Vec::new());
+                }
                 continue;
             }
 
-            if stack.len() == groups.len() - 1 {
-                if springs[end..].iter().all(|&x| x != Spring::Broken) {
-                    count += 1;
+            let groups = line.split(',').map(|x| x.trim().parse()).collect::<Result<Vec<usize>, _>>();
+            let groups = groups.map_err(|_| PuzzleError::BadClue { line: n + 1 })?;
+            sections.last_mut().unwrap().push(groups.into_iter().filter(|&x| x > 0).collect());
+        }
+        if sections.last().unwrap().is_empty() {
+            sections.pop();
+        }
+
+        let mut sections = sections.into_iter();
+        match (sections.next(), sections.next(), sections.next()) {
+            (Some(rows), Some(columns), None) => Ok(Self { rows, columns }),
+            _ => Err(PuzzleError::MissingColumns),
+        }
+    }
+
+    // Get the cells of row or column n as indexes into the grid
+    fn line_cells(&self, line: usize) -> Vec<usize> {
+        let width = self.columns.len();
+        if line < self.rows.len() {
+            (line * width..(line + 1) * width).collect()
+        } else {
+            (line - self.rows.len()..self.rows.len() * width).step_by(width).collect()
+        }
+    }
+
+    // Get the cells of a row or column as indexes into the grid, along with their current contents and the clues
+    fn line(&self, grid: &[Spring], line: usize) -> (Vec<usize>, Row) {
+        let cells = self.line_cells(line);
+        let groups = self.rows.get(line).unwrap_or_else(|| &self.columns[line - self.rows.len()]);
+        let row = Row { springs: cells.iter().map(|&cell| grid[cell]).collect(), groups: groups.clone() };
+        (cells, row)
+    }
+
+    // Mark the row and the column through a cell as needing to be checked again
+    fn dirty_through(&self, cell: usize) -> Vec<bool> {
+        let mut dirty = vec![false; self.rows.len() + self.columns.len()];
+        dirty[cell / self.columns.len()] = true;
+        dirty[self.rows.len() + cell % self.columns.len()] = true;
+        dirty
+    }
+
+    // Fill in every cell that's the same in all arrangements of some row or column, starting from the dirty lines and
+    // going on to lines crossing any cell that gets filled in, until nothing changes. Returns false if some line has
+    // no arrangements left.
+    fn propagate(&self, grid: &mut [Spring], mut dirty: Vec<bool>) -> bool {
+        while let Some(line) = dirty.iter().position(|&x| x) {
+            dirty[line] = false;
+            let (cells, row) = self.line(grid, line);
+            // Counts only overflow u128 for very long lines
+            let Some(forced) = row.forced::<u128>().or_else(|_| row.forced::<BigUint>()).unwrap() else {
+                return false;
+            };
+
+            for (n, &cell) in cells.iter().enumerate() {
+                if grid[cell] != forced[n] {
+                    grid[cell] = forced[n];
+                    dirty[cell / self.columns.len()] = true;
+                    dirty[self.rows.len() + cell % self.columns.len()] = true;
+                }
+            }
+        }
+
+        true
+    }
+
+    // Try both conditions for each unknown cell, and fill in the other one when propagating from a condition leads to
+    // a contradiction, until nothing changes. Returns false if both conditions of some cell do.
+    fn probe(&self, grid: &mut Vec<Spring>) -> bool {
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for cell in 0..grid.len() {
+                if grid[cell] != Spring::Unknown {
+                    continue;
                 }
-                pos += 1;
-            } else {
-                if let Some(old) = cache[stack.len() * springs.len() + pos] {
-                    count += old;
-                    pos += 1;
-                } else {
-                    stack.push((pos, count));
-                    count = 0;
-                    pos = end + 1;
+
+                let possible = [Spring::Broken, Spring::Working].map(|spring| {
+                    let mut guess = grid.clone();
+                    guess[cell] = spring;
+                    self.propagate(&mut guess, self.dirty_through(cell)).then_some(guess)
+                });
+                match possible {
+                    [None, None] => return false,
+                    [Some(guess), None] | [None, Some(guess)] => {
+                        *grid = guess;
+                        changed = true;
+                    }
+                    _ => {}
                 }
             }
         }
 
-        count
-    }).sum()
+        true
+    }
+
+    // Propagate constraints, then guess that the first unknown cell in the unsolved row or column with the fewest
+    // arrangements is broken, and backtrack to it being working if that leads to a contradiction. The stack holds the
+    // grid from before each guess, along with the cell guessed. Once a guess has been wrong, the puzzle is probably
+    // hard enough that it's worth probing every cell before each guess too.
+    fn solve(&self) -> Option<Vec<Spring>> {
+        let lines = self.rows.len() + self.columns.len();
+        let mut grid = vec![Spring::Unknown; self.rows.len() * self.columns.len()];
+        let mut dirty = vec![true; lines];
+        let mut stack = Vec::new();
+        let mut probing = false;
+        loop {
+            let cell = if self.propagate(&mut grid, dirty) && (!probing || self.probe(&mut grid)) {
+                let guess = (0..lines).filter_map(|line| {
+                    let (cells, row) = self.line(&grid, line);
+                    let n = row.springs.iter().position(|&x| x == Spring::Unknown)?;
+                    // Lines with too many arrangements to count in u64 come last
+                    Some((row.count_table::<u64>().map_or(u64::MAX, |table| table[0]), cells[n]))
+                }).min();
+
+                let Some((_, cell)) = guess else {
+                    return Some(grid);
+                };
+                stack.push((grid.clone(), cell));
+                grid[cell] = Spring::Broken;
+                cell
+            } else {
+                let (old_grid, cell) = stack.pop()?;
+                grid = old_grid;
+                grid[cell] = Spring::Working;
+                probing = true;
+                cell
+            };
+            dirty = self.dirty_through(cell);
+        }
+    }
 }
 
 fn part_1(input: &str) -> u64 {
-    arrangements(input, 1)
+    arrangements(input, 1).unwrap()
 }
 
 fn part_2(input: &str) -> u64 {
-    arrangements(input, 5)
+    arrangements(input, 5).unwrap()
 }
 
 fn main() {
     let mut input = String::new();
     std::io::stdin().read_to_string(&mut input).unwrap();
 
+    let mut args = std::env::args().skip(1);
+    if let Some(command) = args.next() {
+        // Solve a nonogram instead
+        if command == "nonogram" {
+            let nonogram = match Nonogram::parse(&input) {
+                Ok(nonogram) => nonogram,
+                Err(err) => {
+                    eprintln!("Invalid nonogram: {}", err);
+                    std::process::exit(1);
+                }
+            };
+
+            let start_time = std::time::Instant::now();
+            let result = nonogram.solve();
+            println!("Nonogram time: {:?}", std::time::Instant::now() - start_time);
+            match result {
+                Some(grid) => {
+                    for row in grid.chunks(nonogram.columns.len()) {
+                        println!("{}", format_springs(row));
+                    }
+                }
+                None => println!("No solution"),
+            }
+            return;
+        }
+
+        // Count all arrangements, with the given unfolding factor, in u64, u128 or arbitrary precision
+        if command == "count" {
+            let repetitions = args.next().unwrap().parse().unwrap();
+            let result = match args.next().as_deref() {
+                Some("u64") | None => arrangements::<u64>(&input, repetitions).map(|x| x.to_string()),
+                Some("u128") => arrangements::<u128>(&input, repetitions).map(|x| x.to_string()),
+                Some("big") => arrangements::<BigUint>(&input, repetitions).map(|x| x.to_string()),
+                Some(x) => panic!("Unknown count type {}", x),
+            };
+            match result {
+                Ok(result) => println!("Arrangements: {}", result),
+                Err(err) => println!("Error: {}", err),
+            }
+            return;
+        }
+
+        // Inspect the arrangements of the row on the given line, numbered from 1
+        let row = Row::parse(input.lines().nth(args.next().unwrap().parse::<usize>().unwrap() - 1).unwrap(), 1);
+        let table = match row.count_table::<u64>() {
+            Ok(table) => table,
+            Err(err) => {
+                eprintln!("Can't rank arrangements: {}", err);
+                std::process::exit(1);
+            }
+        };
+        println!("{} arrangements", table[0]);
+
+        match command.as_str() {
+            "list" => {
+                let limit = args.next().map_or(usize::MAX, |arg| arg.parse().unwrap());
+                for arrangement in row.arrangements().unwrap().take(limit) {
+                    println!("{}", format_springs(&arrangement));
+                }
+            }
+
+            "kth" => {
+                match row.kth(&table, args.next().unwrap().parse().unwrap()) {
+                    Some(arrangement) => println!("{}", format_springs(&arrangement)),
+                    None => println!("No such arrangement"),
+                }
+            }
+
+            "random" => {
+                let mut rng = rand::thread_rng();
+                for _ in 0..args.next().map_or(1, |arg| arg.parse().unwrap()) {
+                    if let Some(arrangement) = row.random(&table, &mut rng) {
+                        println!("{}", format_springs(&arrangement));
+                    }
+                }
+            }
+
+            _ => panic!("Unknown command {}", command),
+        }
+        return;
+    }
+
     let start_time = std::time::Instant::now();
     let result = part_1(&input);
     println!("Part 1 time: {:?}", std::time::Instant::now() - start_time);
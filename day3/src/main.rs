@@ -1,81 +1,262 @@
-use std::io::Read;
+use std::{io::Read, collections::HashMap, fmt};
 
-fn part_1(input: &str) -> u32 {
-    let width = input.find('\n').unwrap();
-    let check = |pos: usize| !["\n", "."].contains(&&input[pos..pos + 1]);
-
-    let mut pos = 0;
-    let mut sum = 0;
-    while let Some(start) = input[pos..].find(|c: char| c.is_ascii_digit()) {
-        let start = start + pos;
-        let end = input[start..].find(|c: char| !c.is_ascii_digit()).unwrap() + start;
-
-        if (start > 0 && check(start - 1)) ||
-           check(end) ||
-           (start > width && (start - width - 1..end - width).any(|pos| check(pos))) ||
-           (start > width + 1 && check(start - width - 2)) ||
-           (start + width < input.len() && (start + width..end + width + 2).any(|pos| check(pos))) {
-            sum += input[start..end].parse::<u32>().unwrap();
+#[derive(Debug, Clone, Copy)]
+enum PartCount {
+    Exactly(usize),
+    AtLeast(usize),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Aggregation {
+    Product,
+    Sum,
+    Max,
+}
+
+#[derive(Debug, Clone)]
+struct GearRules {
+    symbols: Vec<char>,
+    parts: PartCount,
+    aggregation: Aggregation,
+}
+
+impl GearRules {
+    fn standard() -> Self {
+        Self {
+            symbols: vec!['*'],
+            parts: PartCount::Exactly(2),
+            aggregation: Aggregation::Product,
         }
+    }
 
-        pos = end;
+    // Parse rules from the command line, in the form <symbols> <count>[+] [product|sum|max], where a trailing + on the
+    // count means at least that many adjacent parts rather than exactly that many
+    fn from_args<T: Iterator<Item = String>>(mut args: T) -> Result<Option<Self>, RulesError> {
+        let Some(symbols) = args.next() else {
+            return Ok(None);
+        };
+        let count = args.next().ok_or(RulesError::MissingCount)?;
+        let parse_count = |x: &str| x.parse().map_err(|_| RulesError::BadCount(count.clone()));
+        let parts = if let Some(x) = count.strip_suffix('+') {
+            PartCount::AtLeast(parse_count(x)?)
+        } else {
+            PartCount::Exactly(parse_count(&count)?)
+        };
+        let aggregation = match args.next().as_deref() {
+            Some("product") | None => Aggregation::Product,
+            Some("sum") => Aggregation::Sum,
+            Some("max") => Aggregation::Max,
+            Some(x) => return Err(RulesError::BadAggregation(x.to_string())),
+        };
+
+        Ok(Some(Self {
+            symbols: symbols.chars().collect(),
+            parts,
+            aggregation,
+        }))
     }
+}
 
-    sum
+#[derive(Debug)]
+enum RulesError {
+    MissingCount,
+    BadCount(String),
+    BadAggregation(String),
 }
 
-fn part_2(input: &str) -> u32 {
-    let width = input.find('\n').unwrap();
-    let mut counts = vec![(0, 1); input.len()];
-    let mut gears = Vec::new();
-    let mut check = |pos, num: u32| {
-        if &input[pos..pos + 1] == "*" {
-            let (count, product) = &mut counts[pos];
-            if *count == 0 {
-                gears.push(pos);
-            }
-            *count += 1;
-            *product *= num;
+impl fmt::Display for RulesError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::MissingCount => write!(f, "missing part count"),
+            Self::BadCount(count) => write!(f, "invalid part count {}", count),
+            Self::BadAggregation(aggregation) => write!(f, "unknown aggregation {}", aggregation),
         }
-    };
+    }
+}
+
+impl std::error::Error for RulesError {}
 
-    let mut pos = 0;
-    while let Some(start) = input[pos..].find(|c: char| c.is_ascii_digit()) {
-        let start = start + pos;
-        let end = input[start..].find(|c: char| !c.is_ascii_digit()).unwrap() + start;
-        let num = input[start..end].parse().unwrap();
-
-        if start > 0 { check(start - 1, num); }
-        check(end, num);
-        if start > width {
-            for pos in start - width - 1..end - width {
-                check(pos, num);
+#[derive(Debug, Clone, Copy)]
+struct Number {
+    value: u32,
+    row: usize,
+    start: usize,
+    end: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Symbol {
+    c: char,
+    row: usize,
+    col: usize,
+}
+
+#[derive(Debug)]
+enum GridError {
+    Empty,
+    NonAscii { line: usize },
+    RaggedLine { line: usize, expected: usize, found: usize },
+}
+
+impl fmt::Display for GridError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "schematic is empty"),
+            Self::NonAscii { line } => write!(f, "line {} contains non-ASCII characters", line),
+            Self::RaggedLine { line, expected, found } => {
+                write!(f, "line {} is {} characters long, expected {}", line, found, expected)
             }
         }
-        if start > width + 1 { check(start - width - 2, num); }
-        if start + width < input.len() {
-            for pos in start + width..end + width + 2 {
-                check(pos, num);
+    }
+}
+
+impl std::error::Error for GridError {}
+
+struct Grid<'a> {
+    rows: Vec<&'a [u8]>,
+    width: usize,
+}
+
+impl<'a> Grid<'a> {
+    // Split input into rows, accepting both LF and CRLF line endings and ignoring trailing blank lines, and check that
+    // every row has the same width
+    fn parse(input: &'a str) -> Result<Self, GridError> {
+        let mut rows: Vec<_> = input.lines().map(str::as_bytes).collect();
+        while rows.last().is_some_and(|row| row.is_empty()) {
+            rows.pop();
+        }
+
+        let width = rows.first().ok_or(GridError::Empty)?.len();
+        for (n, row) in rows.iter().enumerate() {
+            if !row.is_ascii() {
+                return Err(GridError::NonAscii { line: n + 1 });
+            }
+            if row.len() != width {
+                return Err(GridError::RaggedLine { line: n + 1, expected: width, found: row.len() });
             }
         }
 
-        pos = end;
+        Ok(Self { rows, width })
     }
+}
 
-    gears.into_iter().filter_map(|pos| {
-        let (count, product) = counts[pos];
-        if count == 2 {
-            Some(product)
-        } else {
-            None
+struct Schematic {
+    numbers: Vec<Number>,
+    symbols: Vec<Symbol>,
+    symbols_by_number: Vec<Vec<usize>>,
+    numbers_by_symbol: Vec<Vec<usize>>,
+}
+
+impl Schematic {
+    fn parse(input: &str) -> Result<Self, GridError> {
+        Ok(Self::from_grid(&Grid::parse(input)?))
+    }
+
+    fn from_grid(grid: &Grid) -> Self {
+        let mut numbers = Vec::new();
+        let mut symbols = Vec::new();
+
+        for (row, line) in grid.rows.iter().enumerate() {
+            let mut col = 0;
+
+            while col < grid.width {
+                let c = line[col] as char;
+                if c.is_ascii_digit() {
+                    let end = line[col..].iter().position(|c| !c.is_ascii_digit()).map_or(grid.width, |len| col + len);
+                    let value = line[col..end].iter().fold(0, |acc, &digit| acc * 10 + (digit - b'0') as u32);
+                    numbers.push(Number { value, row, start: col, end });
+                    col = end;
+                } else {
+                    if c != '.' {
+                        symbols.push(Symbol { c, row, col });
+                    }
+                    col += 1;
+                }
+            }
         }
-    }).sum()
+
+        let symbols_by_pos: HashMap<_, _> = symbols.iter().enumerate().map(|(n, symbol)| {
+            ((symbol.row, symbol.col), n)
+        }).collect();
+
+        // Connect each number to every symbol in the box surrounding it
+        let mut numbers_by_symbol = vec![Vec::new(); symbols.len()];
+        let symbols_by_number = numbers.iter().enumerate().map(|(n, number)| {
+            (number.row.saturating_sub(1)..number.row + 2).flat_map(|row| {
+                (number.start.saturating_sub(1)..number.end + 1).map(move |col| (row, col))
+            }).filter_map(|pos| symbols_by_pos.get(&pos).copied()).inspect(|&symbol| {
+                numbers_by_symbol[symbol].push(n);
+            }).collect()
+        }).collect();
+
+        Self {
+            numbers,
+            symbols,
+            symbols_by_number,
+            numbers_by_symbol,
+        }
+    }
+
+    fn part_numbers(&self) -> impl Iterator<Item = &Number> {
+        self.numbers.iter().zip(&self.symbols_by_number).filter_map(|(number, symbols)| {
+            if symbols.is_empty() {
+                None
+            } else {
+                Some(number)
+            }
+        })
+    }
+
+    fn gear_ratios<'a>(&'a self, rules: &'a GearRules) -> impl Iterator<Item = u32> + 'a {
+        self.symbols.iter().enumerate().filter_map(|(n, symbol)| {
+            let count = self.numbers_by_symbol[n].len();
+            if !rules.symbols.contains(&symbol.c) || !match rules.parts {
+                PartCount::Exactly(parts) => count == parts,
+                PartCount::AtLeast(parts) => count >= parts,
+            } {
+                return None;
+            }
+
+            let values = self.adjacent_numbers(n).map(|number| number.value);
+            Some(match rules.aggregation {
+                Aggregation::Product => values.product(),
+                Aggregation::Sum => values.sum(),
+                Aggregation::Max => values.max().unwrap_or(0),
+            })
+        })
+    }
+
+    fn adjacent_numbers(&self, symbol: usize) -> impl Iterator<Item = &Number> {
+        self.numbers_by_symbol[symbol].iter().map(|&n| &self.numbers[n])
+    }
+}
+
+fn part_1(input: &str) -> u32 {
+    Schematic::parse(input).unwrap().part_numbers().map(|number| number.value).sum()
+}
+
+fn part_2(input: &str) -> u32 {
+    Schematic::parse(input).unwrap().gear_ratios(&GearRules::standard()).sum()
 }
 
 fn main() {
     let mut input = String::new();
     std::io::stdin().read_to_string(&mut input).unwrap();
 
+    if let Err(err) = Grid::parse(&input) {
+        eprintln!("Invalid schematic: {}", err);
+        std::process::exit(1);
+    }
+
+    let rules = match GearRules::from_args(std::env::args().skip(1)) {
+        Ok(rules) => rules,
+        Err(err) => {
+            eprintln!("Invalid gear rules: {}", err);
+            eprintln!("Usage: day3 [<symbols> <count>[+] [product|sum|max]]");
+            std::process::exit(1);
+        }
+    };
+
     let start_time = std::time::Instant::now();
     let result = part_1(&input);
     println!("Part 1 time: {:?}", std::time::Instant::now() - start_time);
@@ -85,4 +266,11 @@ fn main() {
     let result = part_2(&input);
     println!("Part 2 time: {:?}", std::time::Instant::now() - start_time);
     println!("Part 2 result: {}", result);
+
+    if let Some(rules) = rules {
+        let start_time = std::time::Instant::now();
+        let result: u32 = Schematic::parse(&input).unwrap().gear_ratios(&rules).sum();
+        println!("Custom gear rules time: {:?}", std::time::Instant::now() - start_time);
+        println!("Custom gear rules result: {}", result);
+    }
 }
@@ -1,25 +1,40 @@
-use std::io::Read;
-
-fn get_matches<'a>(line: &'a str, winning: &mut Vec<&'a str>) -> usize {
-    let mut words = line.split_whitespace().skip(2);
-    winning.clear();
-    while let Some(x) = words.next() {
-        if x == "|" {
-            break;
-        }
+use std::{io::Read, ops::Range, fmt};
+
+struct Card {
+    id: u32,
+    winning: u128,
+    have: u128,
+}
+
+impl Card {
+    fn parse(line: &str) -> Self {
+        let (name, numbers) = line.split_once(':').unwrap();
+        let (winning, have) = numbers.split_once('|').unwrap();
+        let to_bitset = |numbers: &str| numbers.split_whitespace().fold(0u128, |acc, x| {
+            let x: u32 = x.parse().unwrap();
+            assert!(x < u128::BITS, "Card number {} doesn't fit in bitset", x);
+            acc | 1 << x
+        });
 
-        winning.push(x);
+        Self {
+            id: name.trim_start_matches("Card").trim().parse().unwrap(),
+            winning: to_bitset(winning),
+            have: to_bitset(have),
+        }
     }
 
-    words.filter(|x| winning.contains(x)).count()
+    fn matches(&self) -> usize {
+        (self.winning & self.have).count_ones() as usize
+    }
+}
 
+fn parse_cards(input: &str) -> Vec<Card> {
+    input.lines().map(Card::parse).collect()
 }
 
 fn part_1(input: &str) -> u32 {
-    let mut winning = Vec::new();
-
-    input.lines().map(|line| {
-        let matches = get_matches(line, &mut winning);
+    parse_cards(input).iter().map(|card| {
+        let matches = card.matches();
         if matches == 0 {
             0
         } else {
@@ -28,32 +43,98 @@ fn part_1(input: &str) -> u32 {
     }).sum()
 }
 
-fn part_2(input: &str) -> u32 {
-    let mut card_counts = vec![1u32];
-    let mut count = 0;
-    let mut winning = Vec::new();
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum OverflowPolicy {
+    Truncate,
+    Error,
+}
+
+#[derive(Debug)]
+struct OverflowError {
+    id: u32,
+    matches: usize,
+    remaining: usize,
+}
+
+impl fmt::Display for OverflowError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f, "card {} has {} matches, but there are only {} cards after it", self.id, self.matches, self.remaining,
+        )
+    }
+}
+
+impl std::error::Error for OverflowError {}
 
-    for (n, line) in input.lines().enumerate() {
-        let end = n + get_matches(line, &mut winning) + 1;
+struct CardCascade {
+    id: u32,
+    copies: u32,
+    won: Range<usize>,
+}
+
+// Work out how many copies we end up with of each card, and which cards each card won copies of. Each copy of a card
+// wins one copy of every card in its won range.
+fn cascade(cards: &[Card], policy: OverflowPolicy) -> Result<Vec<CardCascade>, OverflowError> {
+    let mut card_counts = vec![1u32; cards.len()];
 
-        if end > card_counts.len() {
-            card_counts.resize(end, 1);
+    cards.iter().enumerate().map(|(n, card)| {
+        let matches = card.matches();
+        let remaining = cards.len() - n - 1;
+        if matches > remaining && policy == OverflowPolicy::Error {
+            return Err(OverflowError { id: card.id, matches, remaining });
         }
 
-        for i in n + 1..end {
-            card_counts[i] += card_counts[n];
+        let won = n + 1..n + 1 + matches.min(remaining);
+        let copies = card_counts[n];
+        for count in &mut card_counts[won.clone()] {
+            *count += copies;
         }
 
-        count += card_counts[n];
-    }
+        Ok(CardCascade { id: card.id, copies, won })
+    }).collect()
+}
+
+fn part_2(input: &str) -> u32 {
+    cascade(&parse_cards(input), OverflowPolicy::Truncate).unwrap().iter().map(|card| card.copies).sum()
+}
+
+fn print_cascade(input: &str, policy: OverflowPolicy) {
+    let cards = parse_cards(input);
 
-    count
+    match cascade(&cards, policy) {
+        Ok(report) => {
+            for card in &report {
+                print!("Card {}: {} copies", card.id, card.copies);
+                if !card.won.is_empty() {
+                    let won: Vec<_> = cards[card.won.clone()].iter().map(|won| won.id.to_string()).collect();
+                    print!(", won copies of cards {}", won.join(", "));
+                }
+                println!();
+            }
+        }
+
+        Err(err) => {
+            eprintln!("Invalid cascade: {}", err);
+            std::process::exit(1);
+        }
+    }
 }
 
 fn main() {
     let mut input = String::new();
     std::io::stdin().read_to_string(&mut input).unwrap();
 
+    let policy = match std::env::args().nth(1).as_deref() {
+        Some("truncate") => Some(OverflowPolicy::Truncate),
+        Some("error") => Some(OverflowPolicy::Error),
+        Some(x) => {
+            eprintln!("Unknown overflow policy {}", x);
+            eprintln!("Usage: day4 [truncate|error]");
+            std::process::exit(1);
+        }
+        None => None,
+    };
+
     let start_time = std::time::Instant::now();
     let result = part_1(&input);
     println!("Part 1 time: {:?}", std::time::Instant::now() - start_time);
@@ -63,4 +144,8 @@ fn main() {
     let result = part_2(&input);
     println!("Part 2 time: {:?}", std::time::Instant::now() - start_time);
     println!("Part 2 result: {}", result);
+
+    if let Some(policy) = policy {
+        print_cascade(&input, policy);
+    }
 }
@@ -1,104 +1,418 @@
-use std::io::Read;
+use std::{io::Read, ops::Range, fmt, collections::{HashMap, VecDeque}};
 
-fn part_1(input: &str) -> u64 {
-    let mut groups = input.split("\n\n");
-    let mut items: Vec<u64> =
-        groups.next().unwrap().split_ascii_whitespace().skip(1).map(|num| num.parse().unwrap()).collect();
-
-    for group in groups {
-        let mappings: Vec<(u64, u64, u64)> = group.lines().skip(1).map(|line| {
-            let mut nums = line.split_ascii_whitespace().map(|num| num.parse().unwrap());
-            let dst = nums.next().unwrap();
-            let src = nums.next().unwrap();
-            let len = nums.next().unwrap();
-            (dst, src, src + len)
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Segment {
+    start: u64,
+    offset: u64,
+}
+
+// A piecewise-linear map over all of u64, stored as sorted segments which each cover the values from their start up
+// to the start of the next segment, and map them by adding a (wrapping) offset. Values outside every mapping range are
+// covered by segments with offset 0.
+#[derive(Debug, Clone)]
+struct RangeMap {
+    segments: Vec<Segment>,
+}
+
+impl RangeMap {
+    fn identity() -> Self {
+        Self { segments: vec![Segment { start: 0, offset: 0 }] }
+    }
+
+    // Build a map from (dst, src, len) triples. If ranges overlap, the first one wins.
+    fn from_ranges(ranges: &[(u64, u64, u64)]) -> Self {
+        let mut map = Self::identity();
+        for &(dst, src, len) in ranges.iter().rev() {
+            map.set(src..src + len, dst.wrapping_sub(src));
+        }
+        map
+    }
+
+    fn segment_end(&self, n: usize) -> u64 {
+        self.segments.get(n + 1).map_or(u64::MAX, |segment| segment.start)
+    }
+
+    fn segment_index(&self, value: u64) -> usize {
+        self.segments.partition_point(|segment| segment.start <= value) - 1
+    }
+
+    // Make sure a segment starts at value, and return its index
+    fn split_at(&mut self, value: u64) -> usize {
+        let n = self.segment_index(value);
+        if self.segments[n].start == value {
+            n
+        } else {
+            self.segments.insert(n + 1, Segment { start: value, offset: self.segments[n].offset });
+            n + 1
+        }
+    }
+
+    fn set(&mut self, range: Range<u64>, offset: u64) {
+        if range.is_empty() {
+            return;
+        }
+
+        let start = self.split_at(range.start);
+        let end = if range.end == u64::MAX { self.segments.len() } else { self.split_at(range.end) };
+        self.segments.splice(start..end, [Segment { start: range.start, offset }]);
+        self.merge();
+    }
+
+    // Merge adjacent segments with the same offset
+    fn merge(&mut self) {
+        self.segments.dedup_by(|segment, prev| segment.offset == prev.offset);
+    }
+
+    fn get(&self, value: u64) -> u64 {
+        value.wrapping_add(self.segments[self.segment_index(value)].offset)
+    }
+
+    // Map a set of ranges, returning the resulting set of ranges, sorted and with adjacent ranges merged
+    fn image(&self, ranges: &[Range<u64>]) -> Vec<Range<u64>> {
+        let mut result: Vec<_> = ranges.iter().filter(|range| !range.is_empty()).flat_map(|range| {
+            (self.segment_index(range.start)..self.segments.len()).take_while(|&n| {
+                self.segments[n].start < range.end
+            }).map(|n| {
+                let offset = self.segments[n].offset;
+                let start = self.segments[n].start.max(range.start);
+                let end = self.segment_end(n).min(range.end);
+                start.wrapping_add(offset)..end.wrapping_add(offset)
+            })
+        }).collect();
+
+        merge_ranges(&mut result);
+        result
+    }
+
+    // Return the set of values which are mapped into the given ranges, sorted and with adjacent ranges merged
+    fn preimage(&self, ranges: &[Range<u64>]) -> Vec<Range<u64>> {
+        let mut result: Vec<_> = self.segments.iter().enumerate().flat_map(|(n, segment)| {
+            let image = segment.start.wrapping_add(segment.offset)..self.segment_end(n).wrapping_add(segment.offset);
+            ranges.iter().filter_map(move |range| {
+                let start = image.start.max(range.start);
+                let end = image.end.min(range.end);
+                if end > start {
+                    Some(start.wrapping_sub(segment.offset)..end.wrapping_sub(segment.offset))
+                } else {
+                    None
+                }
+            })
         }).collect();
 
-        for i in &mut items {
-            for &(dst, src, src_end) in &mappings {
-                if *i >= src && *i < src_end {
-                    *i = *i - src + dst;
+        merge_ranges(&mut result);
+        result
+    }
+
+    // Return a map which applies self, and then next
+    fn compose(&self, next: &RangeMap) -> RangeMap {
+        let mut segments = Vec::new();
+
+        for (n, segment) in self.segments.iter().enumerate() {
+            let start = segment.start.wrapping_add(segment.offset);
+            let end = self.segment_end(n).wrapping_add(segment.offset);
+
+            segments.push(Segment {
+                start: segment.start,
+                offset: segment.offset.wrapping_add(next.segments[next.segment_index(start)].offset),
+            });
+
+            // Add a segment wherever the image of this segment crosses into another segment of next
+            for next_segment in &next.segments[next.segment_index(start) + 1..] {
+                if next_segment.start >= end {
                     break;
                 }
+
+                segments.push(Segment {
+                    start: next_segment.start.wrapping_sub(segment.offset),
+                    offset: segment.offset.wrapping_add(next_segment.offset),
+                });
             }
         }
+
+        let mut map = RangeMap { segments };
+        map.merge();
+        map
+    }
+}
+
+fn merge_ranges(ranges: &mut Vec<Range<u64>>) {
+    ranges.sort_by_key(|range| range.start);
+    let mut merged: Vec<Range<u64>> = Vec::with_capacity(ranges.len());
+    for range in ranges.drain(..) {
+        match merged.last_mut() {
+            Some(last) if last.end >= range.start => { last.end = last.end.max(range.end); }
+            _ => { merged.push(range); }
+        }
     }
+    *ranges = merged;
+}
 
-    items.into_iter().min().unwrap()
+#[derive(Debug)]
+enum AlmanacError {
+    BadHeader(String),
+    NoPath { from: String, to: String },
+    Invalid(Vec<Issue>),
 }
 
-fn part_2(input: &str) -> u64 {
-    let mut groups = input.split("\n\n");
-    let mut nums = groups.next().unwrap().split_ascii_whitespace().skip(1).map(|num| num.parse::<u64>().unwrap());
-    let mut items = Vec::new();
-    while let Some(num) = nums.next() {
-        items.push((num, num + nums.next().unwrap()))
+impl fmt::Display for AlmanacError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::BadHeader(header) => write!(f, "invalid map header \"{}\"", header),
+            Self::NoPath { from, to } => write!(f, "no chain of maps leads from {} to {}", from, to),
+            Self::Invalid(issues) => {
+                write!(f, "{} issues found", issues.len())?;
+                for issue in issues {
+                    write!(f, "\n  {}", issue)?;
+                }
+                Ok(())
+            }
+        }
     }
+}
 
-    let mut new_items = Vec::new();
-    let mut unmapped_items = Vec::new();
+impl std::error::Error for AlmanacError {}
 
-    for group in groups {
-        new_items.clear();
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Validation {
+    Warn,
+    Strict,
+}
 
-        for line in group.lines().skip(1) {
-            unmapped_items.clear();
+// Problems with the ranges of a map. Ranges are numbered from 1, in the order they appear under the map header.
+#[derive(Debug)]
+enum Issue {
+    Overlap { map: String, first: usize, second: usize },
+    ZeroLength { map: String, range: usize },
+    Overflow { map: String, range: usize },
+}
 
-            let mut nums = line.split_ascii_whitespace().map(|num| num.parse::<u64>().unwrap());
-            let dst = nums.next().unwrap();
-            let src = nums.next().unwrap();
-            let len = nums.next().unwrap();
-            let src_end = src + len;
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Overlap { map, first, second } => {
+                write!(f, "{} map: source ranges {} and {} overlap", map, first, second)
+            }
+            Self::ZeroLength { map, range } => write!(f, "{} map: range {} has zero length", map, range),
+            Self::Overflow { map, range } => write!(f, "{} map: range {} extends past the end of u64", map, range),
+        }
+    }
+}
 
-            for &(item, item_end) in &items {
-                unmapped_items.extend([
-                    (item, std::cmp::min(src, item_end)),
-                    (std::cmp::max(src_end, item), item_end),
-                ].into_iter().filter(|&(start, end)| end > start));
+// Check the (dst, src, len) ranges of a map, and clamp the ones that overflow so that they can still be used
+fn validate_ranges(name: &str, ranges: &mut [(u64, u64, u64)], issues: &mut Vec<Issue>) {
+    for (n, (dst, src, len)) in ranges.iter_mut().enumerate() {
+        if *len == 0 {
+            issues.push(Issue::ZeroLength { map: name.to_string(), range: n + 1 });
+        } else if src.checked_add(*len).is_none() || dst.checked_add(*len).is_none() {
+            issues.push(Issue::Overflow { map: name.to_string(), range: n + 1 });
+            *len = (u64::MAX - *src).min(u64::MAX - *dst);
+        }
+    }
 
-                let start = dst + (std::cmp::min(std::cmp::max(src, item), src_end) - src);
-                let end = dst + (std::cmp::max(std::cmp::min(src_end, item_end), src) - src);
-                if end > start {
-                    new_items.push((start, end));
-                }
+    let mut by_start: Vec<_> = (0..ranges.len()).filter(|&n| ranges[n].2 > 0).collect();
+    by_start.sort_unstable_by_key(|&n| ranges[n].1);
+
+    // Compare each range to the one which reaches furthest among those starting before it
+    let mut furthest: Option<usize> = None;
+    for n in by_start {
+        let (_, src, len) = ranges[n];
+        if let Some(prev) = furthest {
+            let (_, prev_src, prev_len) = ranges[prev];
+            if src < prev_src + prev_len {
+                issues.push(Issue::Overlap { map: name.to_string(), first: prev.min(n) + 1, second: prev.max(n) + 1 });
+            }
+            if src + len <= prev_src + prev_len {
+                continue;
             }
+        }
+        furthest = Some(n);
+    }
+}
+
+struct CategoryMap<'a> {
+    source: &'a str,
+    destination: &'a str,
+    map: RangeMap,
+}
+
+struct Almanac<'a> {
+    seeds: Vec<u64>,
+    maps: Vec<CategoryMap<'a>>,
+    issues: Vec<Issue>,
+}
+
+impl<'a> Almanac<'a> {
+    // Parse the almanac, either failing on any issues with the map ranges, or saving them as warnings
+    fn parse(input: &'a str, validation: Validation) -> Result<Self, AlmanacError> {
+        let mut issues = Vec::new();
+        let mut groups = input.split("\n\n");
+        let seeds = groups.next().unwrap().split_ascii_whitespace().skip(1).map(|num| num.parse().unwrap()).collect();
+
+        let maps = groups.map(str::trim).filter(|group| !group.is_empty()).map(|group| {
+            let mut lines = group.lines();
+            let header = lines.next().unwrap();
+            let (source, destination) = header.strip_suffix(" map:").and_then(|name| {
+                name.split_once("-to-")
+            }).ok_or_else(|| AlmanacError::BadHeader(header.to_string()))?;
 
-            std::mem::swap(&mut items, &mut unmapped_items);
+            let mut ranges: Vec<_> = lines.map(|line| {
+                let mut nums = line.split_ascii_whitespace().map(|num| num.parse().unwrap());
+                let dst = nums.next().unwrap();
+                let src = nums.next().unwrap();
+                let len = nums.next().unwrap();
+                (dst, src, len)
+            }).collect();
+            validate_ranges(header.trim_end_matches(" map:"), &mut ranges, &mut issues);
+
+            Ok(CategoryMap { source, destination, map: RangeMap::from_ranges(&ranges) })
+        }).collect::<Result<_, _>>()?;
+
+        if validation == Validation::Strict && !issues.is_empty() {
+            return Err(AlmanacError::Invalid(issues));
         }
 
-        if items.len() > new_items.len() {
-            items.extend(new_items.iter().copied());
-        } else {
-            new_items.extend(items.iter().copied());
-            std::mem::swap(&mut items, &mut new_items);
-        }
-
-        // Merge adjacent ranges, to save time
-        items.sort();
-        new_items.clear();
-        for &i in &items {
-            let (start, end) = i;
-            let mut added = false;
-            if let Some(last) = new_items.last_mut() {
-                if last.1 == start {
-                    last.1 = end;
-                    added = true;
+        Ok(Self { seeds, maps, issues })
+    }
+
+    // Find the shortest chain of maps leading from one category to another, regardless of what order the maps are in
+    fn chain(&self, from: &str, to: &str) -> Result<Vec<&CategoryMap<'a>>, AlmanacError> {
+        let mut came_from = HashMap::new();
+        let mut queue = VecDeque::from([from]);
+
+        while let Some(category) = queue.pop_front() {
+            if category == to {
+                let mut chain = Vec::new();
+                let mut category = to;
+                while let Some(&n) = came_from.get(category) {
+                    let map: &CategoryMap = &self.maps[n];
+                    chain.push(map);
+                    category = map.source;
                 }
+                chain.reverse();
+                return Ok(chain);
             }
-            if !added {
-                new_items.push(i);
+
+            for (n, map) in self.maps.iter().enumerate() {
+                if map.source == category && map.destination != from && !came_from.contains_key(map.destination) {
+                    came_from.insert(map.destination, n);
+                    queue.push_back(map.destination);
+                }
             }
         }
-        std::mem::swap(&mut items, &mut new_items);
+
+        Err(AlmanacError::NoPath { from: from.to_string(), to: to.to_string() })
+    }
+
+    // Compose the chain of maps from seed to location into a single map
+    fn seed_to_location(&self) -> Result<RangeMap, AlmanacError> {
+        Ok(self.chain("seed", "location")?.into_iter().fold(RangeMap::identity(), |acc, map| acc.compose(&map.map)))
+    }
+
+    fn trace(&self, seed: u64) -> Result<Vec<(&'a str, u64)>, AlmanacError> {
+        let mut value = seed;
+        let mut trace = vec![("seed", seed)];
+        for map in self.chain("seed", "location")? {
+            value = map.map.get(value);
+            trace.push((map.destination, value));
+        }
+        Ok(trace)
+    }
+}
+
+fn part_1(input: &str) -> u64 {
+    let almanac = Almanac::parse(input, Validation::Warn).unwrap();
+    let map = almanac.seed_to_location().unwrap();
+
+    almanac.seeds.into_iter().map(|seed| map.get(seed)).min().unwrap()
+}
+
+fn seed_ranges(seeds: &[u64]) -> Vec<Range<u64>> {
+    seeds.chunks(2).map(|pair| pair[0]..pair[0] + pair[1]).collect()
+}
+
+// Find the lowest location reachable from the seed ranges, by going through the segments of the map in order of where
+// they map to, and finding the lowest seed in each which is inside the seed ranges. Once the segments start above the
+// best location found so far, there can't be anything better.
+fn lowest_location_inverse(map: &RangeMap, seed_ranges: &[Range<u64>]) -> Option<u64> {
+    let mut segments: Vec<_> = (0..map.segments.len()).map(|n| {
+        let segment = map.segments[n];
+        (segment.start.wrapping_add(segment.offset), segment.start..map.segment_end(n), segment.offset)
+    }).collect();
+    segments.sort_unstable_by_key(|&(location, _, _)| location);
+
+    let mut best = None;
+    for (location, domain, offset) in segments {
+        if best.is_some_and(|best| location >= best) {
+            break;
+        }
+
+        if let Some(seed) = seed_ranges.iter().filter_map(|range| {
+            let start = range.start.max(domain.start);
+            if start < range.end.min(domain.end) { Some(start) } else { None }
+        }).min() {
+            let location = seed.wrapping_add(offset);
+            best = Some(best.map_or(location, |best: u64| best.min(location)));
+        }
     }
 
-    items.into_iter().min().unwrap().0
+    best
+}
+
+fn part_2(input: &str) -> u64 {
+    let almanac = Almanac::parse(input, Validation::Warn).unwrap();
+    let map = almanac.seed_to_location().unwrap();
+
+    map.image(&seed_ranges(&almanac.seeds))[0].start
+}
+
+fn part_2_inverse(input: &str) -> u64 {
+    let almanac = Almanac::parse(input, Validation::Warn).unwrap();
+    let map = almanac.seed_to_location().unwrap();
+
+    lowest_location_inverse(&map, &seed_ranges(&almanac.seeds)).unwrap()
+}
+
+// Print which seeds, out of all seeds and out of the ones in the seed ranges, end up in the given location range
+fn print_seeds_for_locations(input: &str, locations: Range<u64>) {
+    let almanac = Almanac::parse(input, Validation::Warn).unwrap();
+    let preimage = almanac.seed_to_location().unwrap().preimage(std::slice::from_ref(&locations));
+
+    let in_seed_ranges: Vec<_> = seed_ranges(&almanac.seeds).into_iter().flat_map(|range| {
+        preimage.iter().filter_map(move |seeds| {
+            let start = seeds.start.max(range.start);
+            let end = seeds.end.min(range.end);
+            if end > start { Some(start..end) } else { None }
+        })
+    }).collect();
+
+    println!("Seeds mapping to locations {:?}: {:?}", locations, preimage);
+    println!("Of which in seed ranges: {:?}", in_seed_ranges);
 }
 
 fn main() {
     let mut input = String::new();
     std::io::stdin().read_to_string(&mut input).unwrap();
 
+    let mut args = std::env::args().skip(1).peekable();
+    let validation = if args.next_if(|arg| arg == "--strict").is_some() {
+        Validation::Strict
+    } else {
+        Validation::Warn
+    };
+
+    match Almanac::parse(&input, validation).and_then(|almanac| almanac.seed_to_location().map(|_| almanac)) {
+        Ok(almanac) => {
+            for issue in &almanac.issues {
+                eprintln!("Warning: {}", issue);
+            }
+        }
+
+        Err(err) => {
+            eprintln!("Invalid almanac: {}", err);
+            std::process::exit(1);
+        }
+    }
+
     let start_time = std::time::Instant::now();
     let result = part_1(&input);
     println!("Part 1 time: {:?}", std::time::Instant::now() - start_time);
@@ -108,4 +422,32 @@ fn main() {
     let result = part_2(&input);
     println!("Part 2 time: {:?}", std::time::Instant::now() - start_time);
     println!("Part 2 result: {}", result);
+
+    let start_time = std::time::Instant::now();
+    let result = part_2_inverse(&input);
+    println!("Part 2 (inverse) time: {:?}", std::time::Instant::now() - start_time);
+    println!("Part 2 (inverse) result: {}", result);
+
+    match args.next().as_deref() {
+        Some("trace") => {
+            let almanac = Almanac::parse(&input, Validation::Warn).unwrap();
+            for seed in args.map(|arg| arg.parse().unwrap()) {
+                let trace = almanac.trace(seed).unwrap();
+                let values: Vec<_> = trace.into_iter().map(|(category, value)| {
+                    format!("{} {}", category, value)
+                }).collect();
+                println!("{}", values.join(", "));
+            }
+        }
+
+        Some("locations") => {
+            let mut args = args.map(|arg| arg.parse::<u64>().unwrap());
+            let start = args.next().unwrap();
+            let end = args.next().unwrap_or(start + 1);
+            print_seeds_for_locations(&input, start..end);
+        }
+
+        Some(x) => panic!("Unknown command {}", x),
+        None => {}
+    }
 }
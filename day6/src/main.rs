@@ -1,64 +1,237 @@
-use std::io::Read;
+use std::{io::Read, ops::RangeInclusive};
 
-fn calculate_race(time: f64, distance: f64) -> u32 {
+// Find the button times which beat the best distance, when speed is equal to button time
+fn winning_interval(time: u64, distance: u64) -> Option<RangeInclusive<u64>> {
     // t_a = available time
     // t_b = button time
     // v = velocity = t_b
     // l = traveled distance = (t_a - t_b) * v = (t_a - t_b) * t_b = t_a t_b - t_b^2
     // l_b = best distance
-    // We want to find button held times where traveled distance=best distance, so:
-    // l = l_b ⇔ l_b = t_a t_b - t_b^2 ⇔ 0 = -t_b^2 + t_a t_b - l_b ⇔ 0 = t_b^2 - t_a t_b + l_b ⇔
-    // t_b = t_a/2 ± sqrt((t_a/2)^2 - l_b)
-
-    let root = (time * time / 4.0 - distance).sqrt();
-
-    // Round to floor, time needs to be integer, and in order to reach the needed distance it needs to be greater
-    // than or equal to lower root and lower than or equal to higher root. And we don't care about the actual roots,
-    // only about how many integers are within the bounds.
-
-    // If time is odd, the centerpoint is gonna fall on a halfstep, so the rounding needs to be adjusted by a half.
-    let even = (time as u16) % 2 == 0;
-    let root = if even { root } else { root + 0.5 };
-    // If the root is an integer, the roots will give us exactly the same distance as the best. And we need greater
-    // distance, so exclude those points.
-    let x = if root.fract() == 0.0 { root - 1.0 } else { root }.floor() as u32 * 2;
-    if even { x + 1 } else { x }
+    // We want to find button held times where traveled distance > best distance, so:
+    // t_a t_b - t_b^2 > l_b ⇔ t_b^2 - t_a t_b + l_b < 0 ⇔ (2 t_b - t_a)^2 < t_a^2 - 4 l_b
+    // So the winning button times are strictly between (t_a ± sqrt(t_a^2 - 4 l_b)) / 2
+
+    let (time, distance) = (time as u128, distance as u128);
+    let wins = |button: u128| button * (time - button) > distance;
+
+    let discriminant = match (time * time).checked_sub(4 * distance) {
+        Some(discriminant) if discriminant > 0 => discriminant,
+        _ => return None,
+    };
+
+    // The integer square root gets us within one of the lower bound. Adjust it so that it's the lowest winning button
+    // time, if there is one.
+    let mut lowest = (time - discriminant.isqrt()) / 2;
+    while lowest <= time / 2 && !wins(lowest) {
+        lowest += 1;
+    }
+    while lowest > 0 && wins(lowest - 1) {
+        lowest -= 1;
+    }
+
+    if lowest > time / 2 {
+        None
+    } else {
+        // The distance is symmetric around t_a / 2
+        Some(lowest as u64..=(time - lowest) as u64)
+    }
+}
+
+// Describes how speed depends on how long the button is held. The speed function must be non-decreasing, and such
+// that the distance traveled increases up to some button time and then decreases.
+struct RaceModel {
+    speed: Box<dyn Fn(u64) -> u128>,
+    max_speed: Option<u128>,
+    linear: bool,
+}
+
+impl RaceModel {
+    // The model from the puzzle, where speed is equal to button time
+    fn linear() -> Self {
+        Self { speed: Box::new(|button| button as u128), max_speed: None, linear: true }
+    }
+
+    fn from_fn<F: Fn(u64) -> u128 + 'static>(speed: F) -> Self {
+        Self { speed: Box::new(speed), max_speed: None, linear: false }
+    }
+
+    // Speed is c_0 + c_1 t_b + c_2 t_b^2 + ...
+    fn polynomial(coefficients: &[u64]) -> Self {
+        if coefficients == [0, 1] {
+            return Self::linear();
+        }
+
+        let coefficients = coefficients.to_vec();
+        Self::from_fn(move |button| coefficients.iter().rev().fold(0u128, |acc, &c| {
+            acc.saturating_mul(button as u128).saturating_add(c as u128)
+        }))
+    }
+
+    fn with_max_speed(self, max_speed: u128) -> Self {
+        Self { max_speed: Some(max_speed), linear: false, ..self }
+    }
+
+    fn distance(&self, time: u64, button: u64) -> u128 {
+        let speed = (self.speed)(button);
+        let speed = self.max_speed.map_or(speed, |max_speed| speed.min(max_speed));
+        speed.saturating_mul((time - button) as u128)
+    }
+
+    fn winning_interval(&self, time: u64, distance: u64) -> Option<RangeInclusive<u64>> {
+        if self.linear {
+            winning_interval(time, distance)
+        } else {
+            self.search_interval(time, distance)
+        }
+    }
+
+    // Find the button time giving the furthest distance, by binary searching for where the distance stops increasing,
+    // and then binary search on either side of it for where the best distance is beaten
+    fn search_interval(&self, time: u64, distance: u64) -> Option<RangeInclusive<u64>> {
+        let distance = distance as u128;
+        let first_true = |mut low: u64, mut high: u64, pred: &dyn Fn(u64) -> bool| {
+            while low < high {
+                let mid = low + (high - low) / 2;
+                if pred(mid) {
+                    high = mid;
+                } else {
+                    low = mid + 1;
+                }
+            }
+            low
+        };
+
+        let peak = first_true(0, time, &|button| self.distance(time, button + 1) <= self.distance(time, button));
+        if self.distance(time, peak) <= distance {
+            return None;
+        }
+
+        let lowest = first_true(0, peak, &|button| self.distance(time, button) > distance);
+        let highest = first_true(peak, time, &|button| self.distance(time, button + 1) <= distance);
+        Some(lowest..=highest)
+    }
+
+    fn calculate_race(&self, time: u64, distance: u64) -> u64 {
+        self.winning_interval(time, distance).map_or(0, |interval| interval.end() - interval.start() + 1)
+    }
 }
 
-fn part_1(input: &str) -> u32 {
+fn part_1(input: &str, model: &RaceModel) -> u64 {
     // Iterate over races
     let mut lines = input.lines().map(|line| {
-        line.split_ascii_whitespace().skip(1).map(|num| num.parse::<f64>().unwrap())
+        line.split_ascii_whitespace().skip(1).map(|num| num.parse::<u64>().unwrap())
     });
     let times = lines.next().unwrap();
     let distances = lines.next().unwrap();
 
     times.zip(distances).map(|(time, distance)| {
-        calculate_race(time, distance)
+        model.calculate_race(time, distance)
     }).product()
 }
 
-fn part_2(input: &str) -> u32 {
+fn part_2(input: &str, model: &RaceModel) -> u64 {
     let mut nums = input.lines().map(|line| {
-        line.splitn(2, ' ').nth(1).unwrap().chars().filter(|x| x.is_ascii_digit()).fold(0.0, |acc, x| {
-            acc * 10.0 + x.to_digit(10).unwrap() as f64
+        line.split_once(' ').unwrap().1.chars().filter(|x| x.is_ascii_digit()).fold(0, |acc, x| {
+            acc * 10 + x.to_digit(10).unwrap() as u64
         })
     });
 
-    calculate_race(nums.next().unwrap(), nums.next().unwrap())
+    model.calculate_race(nums.next().unwrap(), nums.next().unwrap())
 }
 
 fn main() {
+    let mut args = std::env::args().skip(1);
+    let model = match args.next().as_deref() {
+        // Speed as a polynomial of button time, given as comma separated coefficients starting with the constant
+        // term, and optionally a max speed
+        Some("model") => {
+            let coefficients: Vec<_> = args.next().unwrap().split(',').map(|c| c.parse().unwrap()).collect();
+            let model = RaceModel::polynomial(&coefficients);
+            match args.next() {
+                Some(max_speed) => model.with_max_speed(max_speed.parse().unwrap()),
+                None => model,
+            }
+        }
+
+        Some(x) => panic!("Unknown command {}", x),
+        None => RaceModel::linear(),
+    };
+
     let mut input = String::new();
     std::io::stdin().read_to_string(&mut input).unwrap();
 
     let start_time = std::time::Instant::now();
-    let result = part_1(&input);
+    let result = part_1(&input, &model);
     println!("Part 1 time: {:?}", std::time::Instant::now() - start_time);
     println!("Part 1 result: {}", result);
 
     let start_time = std::time::Instant::now();
-    let result = part_2(&input);
+    let result = part_2(&input, &model);
     println!("Part 2 time: {:?}", std::time::Instant::now() - start_time);
     println!("Part 2 result: {}", result);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn brute_force_race(model: &RaceModel, time: u64, distance: u64) -> u64 {
+        (0..=time).filter(|&button| model.distance(time, button) > distance as u128).count() as u64
+    }
+
+    // Compare against brute force for every race with time up to 200. The number of winning button times only changes
+    // at distances the model can reach, so check those, and the distances either side of them.
+    fn check_model(model: RaceModel) {
+        for time in 0..=200 {
+            let mut distances: Vec<_> = (0..=time).flat_map(|button| {
+                let distance = model.distance(time, button) as u64;
+                [distance.saturating_sub(1), distance, distance + 1]
+            }).collect();
+            distances.sort_unstable();
+            distances.dedup();
+
+            for distance in distances {
+                let expected = brute_force_race(&model, time, distance);
+                assert_eq!(model.calculate_race(time, distance), expected, "time {}, distance {}", time, distance);
+            }
+        }
+    }
+
+    #[test]
+    fn closed_form_matches_brute_force() {
+        check_model(RaceModel::linear());
+    }
+
+    #[test]
+    fn search_matches_brute_force() {
+        check_model(RaceModel::from_fn(|button| button as u128));
+    }
+
+    #[test]
+    fn polynomial_matches_brute_force() {
+        check_model(RaceModel::polynomial(&[0, 0, 1]));
+        check_model(RaceModel::polynomial(&[3, 2, 1]));
+    }
+
+    #[test]
+    fn max_speed_matches_brute_force() {
+        for max_speed in [0, 1, 20, 100, 1000] {
+            check_model(RaceModel::linear().with_max_speed(max_speed));
+            check_model(RaceModel::polynomial(&[0, 0, 1]).with_max_speed(max_speed));
+        }
+    }
+
+    #[test]
+    fn saturating_speed_matches_brute_force() {
+        // Speed saturates for button times from 20, and distances from then on are too far to fit in a u64
+        let mut coefficients = vec![0; 31];
+        coefficients[30] = 1;
+        let model = RaceModel::polynomial(&coefficients);
+        for time in 0..=200 {
+            for distance in [0, 1 << 40, u64::MAX] {
+                let expected = brute_force_race(&model, time, distance);
+                assert_eq!(model.calculate_race(time, distance), expected, "time {}, distance {}", time, distance);
+            }
+        }
+    }
+}
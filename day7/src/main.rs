@@ -1,6 +1,6 @@
-use std::io::Read;
+use std::{io::Read, str::FromStr, fmt, cmp::Reverse};
 
-#[derive(PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 enum HandType {
     HighCard,
     OnePair,
@@ -11,44 +11,20 @@ enum HandType {
     FiveOfAKind,
 }
 
-fn winnings(input: &str, jokers: bool) -> u32 {
-    // Parse input
-    let mut hands: Vec<_> = input.lines().map(|line| {
-        let mut words = line.split_ascii_whitespace();
-        (words.next().unwrap(), words.next().unwrap().parse::<u16>().unwrap())
-    }).collect();
-
-    // Sort by hand type and hand card values
-    hands.sort_by_cached_key(|&(hand_src, _)| {
-        // Translate card names to values
-        let mut hand = [0u8; 5];
-        for (src, dst) in hand_src.chars().zip(&mut hand) {
-            *dst = match src {
-                '2'..='9' => (src.to_digit(10).unwrap() - 1) as u8,
-                'T' => 9,
-                'J' => if jokers { 0 } else { 10 },
-                'Q' => 11,
-                'K' => 12,
-                'A' => 13,
-                _ => panic!(),
-            };
-        }
+const HAND_TYPES: [HandType; 7] = [
+    HandType::HighCard,
+    HandType::OnePair,
+    HandType::TwoPairs,
+    HandType::ThreeOfAKind,
+    HandType::FullHouse,
+    HandType::FourOfAKind,
+    HandType::FiveOfAKind,
+];
 
-        // Count how many occurrences there are of each card
-        let mut counts = [0u8; 14];
-        for &i in &hand {
-            counts[i as usize] += 1;
-        }
-
-        // Change type of jokers to match whatever we have most of
-        if jokers {
-            let max_index = counts.iter().enumerate().skip(1).max_by_key(|(_, &x)| x).unwrap().0;
-            counts[max_index] += counts[0];
-            counts[0] = 0;
-        }
-
-        // Calculate type of hand
-        let hand_type = if counts.contains(&5) {
+impl HandType {
+    // Classify a hand from how many occurrences there are of each card
+    fn from_counts(counts: &[u8]) -> Self {
+        if counts.contains(&5) {
             HandType::FiveOfAKind
         } else if counts.contains(&4) {
             HandType::FourOfAKind
@@ -66,10 +42,163 @@ fn winnings(input: &str, jokers: bool) -> u32 {
             }
         } else {
             HandType::HighCard
-        };
+        }
+    }
+}
+
+#[derive(Debug)]
+enum ParseHandError {
+    Length(usize),
+    UnknownCard(char),
+}
+
+impl fmt::Display for ParseHandError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Length(len) => write!(f, "hand has {} cards, expected 5", len),
+            Self::UnknownCard(card) => write!(f, "unknown card {}", card),
+        }
+    }
+}
+
+impl std::error::Error for ParseHandError {}
+
+// Change type of wilds to match whatever we have most of
+fn join_most_common(counts: &mut [u8], wilds: u8) {
+    let max_index = counts.iter().enumerate().max_by_key(|(_, &x)| x).unwrap().0;
+    counts[max_index] += wilds;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TieBreak {
+    // Compare cards in the order they appear in the hand
+    CardOrder,
+    // Compare cards in order of group size and then value, like in poker, with wild cards last
+    GroupRank,
+}
+
+struct Rules {
+    // Card names, from lowest to highest
+    order: Vec<char>,
+    wild: Vec<char>,
+    // Takes the number of each non-wild card, indexed by card value, and the number of wilds, and adds the wilds to
+    // the counts
+    upgrade: fn(&mut [u8], u8),
+    tie_break: TieBreak,
+}
+
+impl Rules {
+    fn standard() -> Self {
+        Self {
+            order: "23456789TJQKA".chars().collect(),
+            wild: vec![],
+            upgrade: join_most_common,
+            tie_break: TieBreak::CardOrder,
+        }
+    }
+
+    fn jokers() -> Self {
+        Self {
+            order: "J23456789TQKA".chars().collect(),
+            wild: vec!['J'],
+            upgrade: join_most_common,
+            tie_break: TieBreak::CardOrder,
+        }
+    }
+
+    fn with_tie_break(self, tie_break: TieBreak) -> Self {
+        Self { tie_break, ..self }
+    }
+
+    // Translate card names to values, and count how many occurrences there are of each non-wild card, and of wilds
+    fn parse_cards(&self, hand_src: &str) -> Result<([u8; 5], Vec<u8>, u8), ParseHandError> {
+        let cards: Vec<_> = hand_src.chars().collect();
+        if cards.len() != 5 {
+            return Err(ParseHandError::Length(cards.len()));
+        }
+        let mut values = [0u8; 5];
+        for (&src, dst) in cards.iter().zip(&mut values) {
+            *dst = self.order.iter().position(|&card| card == src).ok_or(ParseHandError::UnknownCard(src))? as u8;
+        }
+
+        let mut counts = vec![0u8; self.order.len()];
+        let mut wilds = 0;
+        for &value in &values {
+            if self.wild.contains(&self.order[value as usize]) {
+                wilds += 1;
+            } else {
+                counts[value as usize] += 1;
+            }
+        }
+
+        Ok((values, counts, wilds))
+    }
 
-        (hand_type, hand)
-    });
+    fn parse_hand(&self, hand_src: &str) -> Result<Hand, ParseHandError> {
+        let (cards, mut counts, wilds) = self.parse_cards(hand_src)?;
+
+        let mut values = cards;
+        if self.tie_break == TieBreak::GroupRank {
+            values.sort_unstable_by_key(|&value| {
+                let wild = self.wild.contains(&self.order[value as usize]);
+                Reverse((!wild, counts[value as usize], value))
+            });
+        }
+
+        // Let the wilds change type
+        (self.upgrade)(&mut counts, wilds);
+
+        Ok(Hand { hand_type: HandType::from_counts(&counts), values, cards })
+    }
+
+    // Get the type a hand would have if wilds were ordinary cards, and the type it actually has
+    fn substitution(&self, hand_src: &str) -> Result<(HandType, HandType), ParseHandError> {
+        let (values, mut counts, wilds) = self.parse_cards(hand_src)?;
+
+        let mut base_counts = counts.clone();
+        for value in values {
+            if self.wild.contains(&self.order[value as usize]) {
+                base_counts[value as usize] += 1;
+            }
+        }
+        (self.upgrade)(&mut counts, wilds);
+
+        Ok((HandType::from_counts(&base_counts), HandType::from_counts(&counts)))
+    }
+}
+
+// A hand, ordered by type and then by card values. Only hands parsed with the same rules can be meaningfully compared.
+// When ranking by groups, hands with the same groups are then ordered by their cards in the original order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Hand {
+    hand_type: HandType,
+    values: [u8; 5],
+    cards: [u8; 5],
+}
+
+impl Hand {
+    fn hand_type(&self) -> HandType {
+        self.hand_type
+    }
+}
+
+impl FromStr for Hand {
+    type Err = ParseHandError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Rules::standard().parse_hand(s)
+    }
+}
+
+fn winnings(input: &str, rules: &Rules) -> u32 {
+    // Parse input
+    let mut hands: Vec<_> = input.lines().map(|line| {
+        let mut words = line.split_ascii_whitespace();
+        (rules.parse_hand(words.next().unwrap()).unwrap(), words.next().unwrap().parse::<u16>().unwrap())
+    }).collect();
+
+    // Sort by hand type and hand card values
+    hands.sort_unstable_by_key(|&(hand, _)| hand);
 
     // Sum up winnings
     hands.iter().enumerate().map(|(n, &(_, bid))| {
@@ -77,25 +206,77 @@ fn winnings(input: &str, jokers: bool) -> u32 {
     }).sum()
 }
 
-fn part_1(input: &str) -> u32 {
-    winnings(input, false)
+fn print_statistics(input: &str, rules: &Rules) {
+    let mut by_type = [0u32; 7];
+    let mut moved = [[0u32; 7]; 7];
+
+    for line in input.lines() {
+        let (base_type, hand_type) = rules.substitution(line.split_ascii_whitespace().next().unwrap()).unwrap();
+        by_type[hand_type as usize] += 1;
+        moved[base_type as usize][hand_type as usize] += 1;
+    }
+
+    for (hand_type, count) in HAND_TYPES.iter().zip(by_type) {
+        println!("{:?}: {}", hand_type, count);
+    }
+
+    for (from, row) in HAND_TYPES.iter().zip(moved) {
+        for (to, count) in HAND_TYPES.iter().zip(row) {
+            if from != to && count > 0 {
+                println!("{:?} -> {:?}: {}", from, to, count);
+            }
+        }
+    }
+}
+
+fn part_1(input: &str, tie_break: TieBreak) -> u32 {
+    winnings(input, &Rules::standard().with_tie_break(tie_break))
 }
 
-fn part_2(input: &str) -> u32 {
-    winnings(input, true)
+fn part_2(input: &str, tie_break: TieBreak) -> u32 {
+    winnings(input, &Rules::jokers().with_tie_break(tie_break))
 }
 
 fn main() {
+    let mut args = std::env::args().skip(1);
+    let mut tie_break = TieBreak::CardOrder;
+    let mut statistics = false;
+
+    match args.next().as_deref() {
+        // Classify the given hands, under the standard rules
+        Some("classify") => {
+            for arg in args {
+                match arg.parse::<Hand>() {
+                    Ok(hand) => println!("{}: {:?}", arg, hand.hand_type()),
+                    Err(err) => println!("{}: {}", arg, err),
+                }
+            }
+            return;
+        }
+
+        Some("poker") => { tie_break = TieBreak::GroupRank; }
+        Some("stats") => { statistics = true; }
+        Some(x) => panic!("Unknown command {}", x),
+        None => {}
+    }
+
     let mut input = String::new();
     std::io::stdin().read_to_string(&mut input).unwrap();
 
     let start_time = std::time::Instant::now();
-    let result = part_1(&input);
+    let result = part_1(&input, tie_break);
     println!("Part 1 time: {:?}", std::time::Instant::now() - start_time);
     println!("Part 1 result: {}", result);
 
     let start_time = std::time::Instant::now();
-    let result = part_2(&input);
+    let result = part_2(&input, tie_break);
     println!("Part 2 time: {:?}", std::time::Instant::now() - start_time);
     println!("Part 2 result: {}", result);
+
+    if statistics {
+        println!("Standard rules:");
+        print_statistics(&input, &Rules::standard());
+        println!("Joker rules:");
+        print_statistics(&input, &Rules::jokers());
+    }
 }
@@ -1,134 +1,417 @@
-use std::{io::Read, collections::HashMap};
-
-fn parse_node(src: &str) -> u16 {
-    src.chars().fold(0, |acc, c| {
-        (acc << 5) | (c as u16 - 'A' as u16)
-    })
-}
-
-fn part_1(input: &str) -> u16 {
-    let mut lines = input.lines();
-    let directions = lines.next().unwrap();
-    let start = parse_node("AAA");
-    let goal = parse_node("ZZZ");
-    let mut nodes = vec![(0, 0); goal as usize + 1];
-    for line in lines.skip(1) {
-        nodes[parse_node(&line[0..3]) as usize] = (parse_node(&line[7..10]), parse_node(&line[12..15]));
-    }
-    directions.chars().cycle().zip(1..).try_fold(start, |pos, (direction, n)| {
-        let node = nodes[pos as usize];
-        let new_pos = match direction {
-            'L' => node.0,
-            'R' => node.1,
-            _ => panic!(),
-        };
-        if new_pos == goal {
-            Err(n)
-        } else {
-            Ok(new_pos)
+use std::{io::Read, fmt, collections::{HashMap, hash_map::Entry::{Vacant, Occupied}}};
+
+#[derive(Debug)]
+enum ParseError {
+    Empty,
+    BadLine(usize),
+    BadName { line: usize, name: String },
+    BadDirection(char),
+    Undefined(String),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "no directions"),
+            Self::BadLine(line) => write!(f, "line {} isn't of the form NAME = (LEFT, RIGHT)", line),
+            Self::BadName { line, name } => write!(f, "line {}: invalid node name \"{}\"", line, name),
+            Self::BadDirection(direction) => write!(f, "invalid direction {}", direction),
+            Self::Undefined(name) => write!(f, "node {} is used but never defined", name),
         }
-    }).err().unwrap()
+    }
 }
 
+impl std::error::Error for ParseError {}
+
 #[derive(Debug)]
-struct Cycle {
-    period: u16,
-    offset: u16,
-    goal_offsets: Vec<u16>,
+struct Overflow;
+
+impl fmt::Display for Overflow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "number of steps doesn't fit in 64 bits")
+    }
 }
 
-fn part_2(input: &str) -> u64 {
-    let mut lines = input.lines();
-    let directions = lines.next().unwrap();
-    let mut nodes = vec![(0, 0); parse_node("ZZZ") as usize + 1];
+impl std::error::Error for Overflow {}
 
-    let starts: Vec<_> = lines.skip(1).filter_map(|line| {
-        let pos = parse_node(&line[0..3]);
-        nodes[pos as usize] = (parse_node(&line[7..10]), parse_node(&line[12..15]));
-        if pos & 0x1F == 0 {
-            Some(pos)
-        } else {
-            None
+struct Network<'a> {
+    directions: &'a str,
+    names: Vec<&'a str>,
+    nodes: Vec<(usize, usize)>,
+}
+
+impl<'a> Network<'a> {
+    fn parse(input: &'a str) -> Result<Self, ParseError> {
+        let mut lines = input.lines();
+        let directions = lines.next().map(str::trim).filter(|x| !x.is_empty()).ok_or(ParseError::Empty)?;
+        if let Some(direction) = directions.chars().find(|&c| c != 'L' && c != 'R') {
+            return Err(ParseError::BadDirection(direction));
         }
-    }).collect();
 
-    let mut visited = HashMap::new();
-
-    let mut cycles: Vec<_> = starts.into_iter().map(|start| {
-        visited.clear();
-        directions.chars().enumerate().cycle().enumerate().try_fold((start, Vec::new()), |
-            (pos, mut offsets),
-            (step, (step_in_cycle, direction)),
-        | {
-            visited.insert((pos, step_in_cycle), step as u16);
-            let node = nodes[pos as usize];
-            let new_pos = match direction {
+        let mut indexes_by_name = HashMap::new();
+        let mut names = Vec::new();
+        let mut get_index = |name: &'a str, line: usize| {
+            if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric()) {
+                return Err(ParseError::BadName { line, name: name.to_string() });
+            }
+
+            Ok(match indexes_by_name.entry(name) {
+                Vacant(entry) => {
+                    let index = names.len();
+                    entry.insert(index);
+                    names.push(name);
+                    index
+                },
+                Occupied(entry) => *entry.get(),
+            })
+        };
+
+        let mut nodes = Vec::new();
+        for (n, line) in lines.enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+            let line_number = n + 2;
+            let (name, targets) = line.split_once('=').ok_or(ParseError::BadLine(line_number))?;
+            let (left, right) = targets.trim().strip_prefix('(').and_then(|x| x.strip_suffix(')')).and_then(|x| {
+                x.split_once(',')
+            }).ok_or(ParseError::BadLine(line_number))?;
+
+            let index = get_index(name.trim(), line_number)?;
+            let node = (get_index(left.trim(), line_number)?, get_index(right.trim(), line_number)?);
+            nodes.push((index, node));
+        }
+
+        let mut defined = vec![None; names.len()];
+        for (index, node) in nodes {
+            defined[index] = Some(node);
+        }
+        let nodes = defined.into_iter().enumerate().map(|(n, node)| {
+            node.ok_or_else(|| ParseError::Undefined(names[n].to_string()))
+        }).collect::<Result<_, _>>()?;
+
+        Ok(Self { directions, names, nodes })
+    }
+
+    fn find_all(&self, node_match: &NodeMatch) -> Vec<usize> {
+        (0..self.names.len()).filter(|&n| node_match.matches(self.names[n])).collect()
+    }
+
+    fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph network {\n");
+        for (n, &(left, right)) in self.nodes.iter().enumerate() {
+            if left == right {
+                dot += &format!("    \"{}\" -> \"{}\" [label=\"L/R\"];\n", self.names[n], self.names[left]);
+            } else {
+                dot += &format!("    \"{}\" -> \"{}\" [label=\"L\"];\n", self.names[n], self.names[left]);
+                dot += &format!("    \"{}\" -> \"{}\" [label=\"R\"];\n", self.names[n], self.names[right]);
+            }
+        }
+        dot += "}\n";
+        dot
+    }
+
+    // Find which nodes can be reached from any of the given nodes, taking any direction at every step
+    fn reachable(&self, from: &[usize]) -> Vec<bool> {
+        let mut reachable = vec![false; self.nodes.len()];
+        let mut to_visit = from.to_vec();
+        while let Some(pos) = to_visit.pop() {
+            if !reachable[pos] {
+                reachable[pos] = true;
+                to_visit.extend([self.nodes[pos].0, self.nodes[pos].1]);
+            }
+        }
+        reachable
+    }
+
+    // Get the node after each step, when following the directions from the given node
+    fn walk(&self, start: usize) -> impl Iterator<Item = usize> + '_ {
+        self.directions.chars().cycle().scan(start, |pos, direction| {
+            let node = self.nodes[*pos];
+            *pos = match direction {
                 'L' => node.0,
                 'R' => node.1,
                 _ => panic!(),
             };
-            if new_pos & 0x1F == 'Z' as u16 - 'A' as u16 {
-                offsets.push(step as u16 + 1);
-            }
-            if let Some(&x) = visited.get(&(new_pos, step_in_cycle + 1)) {
-                for i in &mut offsets {
-                    *i -= x;
-                }
-                Err(Cycle {
-                    period: step as u16 + 1 - x,
-                    offset: x as u16,
-                    goal_offsets: offsets,
-                })
-            } else {
-                Ok((new_pos, offsets))
-            }
-        }).err().unwrap()
-    }).collect();
+            Some(*pos)
+        })
+    }
+}
+
+// Which nodes to start from or to stop at. On the command line, "*Z" matches names ending with Z, while other
+// arguments match names exactly.
+#[derive(Debug, Clone)]
+enum NodeMatch {
+    Exact(String),
+    EndsWith(String),
+}
 
-    let starting_offset = cycles.iter().map(|cycle| cycle.offset).max().unwrap();
-    for cycle in &mut cycles {
-        let cycle_starting_offset = starting_offset - cycle.offset;
-        let offset_in_cycle = (cycle_starting_offset % cycle.period) as u16;
-        for goal_offset in &mut cycle.goal_offsets {
-            *goal_offset = (*goal_offset as u16 + cycle.period as u16 - offset_in_cycle) % cycle.period as u16;
+impl NodeMatch {
+    fn parse(src: &str) -> Self {
+        match src.strip_prefix('*') {
+            Some(suffix) => Self::EndsWith(suffix.to_string()),
+            None => Self::Exact(src.to_string()),
         }
-        cycle.offset = 0;
     }
 
-    let mut new_offsets = Vec::new();
+    fn matches(&self, name: &str) -> bool {
+        match self {
+            Self::Exact(x) => name == x,
+            Self::EndsWith(suffix) => name.ends_with(suffix.as_str()),
+        }
+    }
+}
+
+// The steps at which a ghost is on a goal node. Once the ghost has taken start steps it's in a loop, repeating every
+// period steps. Goals hit before that are listed in prefix_goals, and the goals in the loop in goal_residues, as
+// residues modulo period.
+#[derive(Debug)]
+struct Cycle {
+    start: u64,
+    period: u64,
+    prefix_goals: Vec<u64>,
+    goal_residues: Vec<u64>,
+}
 
-    let full_cycle = cycles.into_iter().fold((1, vec![0]), |(acc_period, mut acc_offsets), cycle| {
-        let mut pos = 0;
-        let cycle_period = cycle.period as u64;
-        new_offsets.clear();
-        loop {
-            for &i in &acc_offsets {
-                if cycle.goal_offsets.contains(&(((pos + i) % cycle_period) as u16)) {
-                    new_offsets.push(pos + i);
-                }
+impl Cycle {
+    fn find(network: &Network, start: usize, is_goal: &[bool]) -> Self {
+        let mut visited = HashMap::new();
+        let mut goals = Vec::new();
+        let mut pos = start;
+
+        // Walk until we get back to a node we've been on, at the same point in the directions
+        for (step, (step_in_directions, direction)) in network.directions.chars().enumerate().cycle().enumerate() {
+            if let Some(&cycle_start) = visited.get(&(pos, step_in_directions)) {
+                let (period, cycle_start) = ((step - cycle_start) as u64, cycle_start as u64);
+                let (prefix_goals, cycle_goals): (Vec<_>, Vec<_>) = goals.into_iter().partition(|&goal| {
+                    goal < cycle_start
+                });
+
+                return Self {
+                    start: cycle_start,
+                    period,
+                    prefix_goals,
+                    goal_residues: cycle_goals.into_iter().map(|goal| goal % period).collect(),
+                };
             }
-            pos += acc_period;
-            if pos % cycle_period == 0 {
-                std::mem::swap(&mut new_offsets, &mut acc_offsets);
-                break (pos, acc_offsets);
+
+            visited.insert((pos, step_in_directions), step);
+            if is_goal[pos] {
+                goals.push(step as u64);
             }
+
+            let node = network.nodes[pos];
+            pos = match direction {
+                'L' => node.0,
+                'R' => node.1,
+                _ => panic!(),
+            };
+        }
+
+        unreachable!()
+    }
+
+    fn is_goal(&self, step: u64) -> bool {
+        if step < self.start {
+            self.prefix_goals.contains(&step)
+        } else {
+            self.goal_residues.contains(&(step % self.period))
         }
-    });
+    }
+}
+
+fn gcd(a: i128, b: i128) -> i128 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+// Returns (g, x) such that a x ≡ g (mod b), where g = gcd(a, b)
+fn extended_gcd(a: i128, b: i128) -> (i128, i128) {
+    let (mut old_r, mut r) = (a, b);
+    let (mut old_s, mut s) = (1, 0);
+    while r != 0 {
+        let quotient = old_r / r;
+        (old_r, r) = (r, old_r - quotient * r);
+        (old_s, s) = (s, old_s - quotient * s);
+    }
+    (old_r, old_s)
+}
+
+// Combine x ≡ a.0 (mod a.1) and x ≡ b.0 (mod b.1) into a single congruence, with moduli that don't have to be
+// coprime. Returns None if there's no solution.
+fn crt((r1, m1): (i128, i128), (r2, m2): (i128, i128)) -> Result<Option<(i128, i128)>, Overflow> {
+    let g = gcd(m1, m2);
+    if (r2 - r1) % g != 0 {
+        return Ok(None);
+    }
+
+    let (_, inverse) = extended_gcd(m1 / g, m2 / g);
+    let lcm = (m1 / g).checked_mul(m2).ok_or(Overflow)?;
+    let k = ((r2 - r1) / g % (m2 / g)).checked_mul(inverse).ok_or(Overflow)?.rem_euclid(m2 / g);
+    let x = m1.checked_mul(k).and_then(|x| x.checked_add(r1)).ok_or(Overflow)?;
+    Ok(Some((x.rem_euclid(lcm), lcm)))
+}
+
+// Find the first step after the start where every ghost is on a goal node, or None if that never happens
+fn first_common_goal(cycles: &[Cycle]) -> Result<Option<u64>, Overflow> {
+    let Some(latest_start) = cycles.iter().max_by_key(|cycle| cycle.start) else {
+        return Ok(None);
+    };
+
+    // Before every ghost is in its loop, the answer has to be one of the prefix goals of the ghost that enters its
+    // loop last
+    if let Some(&step) = latest_start.prefix_goals.iter().find(|&&step| {
+        step > 0 && cycles.iter().all(|cycle| cycle.is_goal(step))
+    }) {
+        return Ok(Some(step));
+    }
+
+    // After that, combine the loops of all ghosts, for every combination of goals
+    let mut congruences = vec![(0, 1)];
+    for cycle in cycles {
+        congruences = congruences.into_iter().flat_map(|congruence| {
+            cycle.goal_residues.iter().map(move |&residue| {
+                crt(congruence, (residue as i128, cycle.period as i128))
+            })
+        }).filter_map(Result::transpose).collect::<Result<_, _>>()?;
+        congruences.sort_unstable();
+        congruences.dedup();
+    }
+
+    let first_step = latest_start.start.max(1) as i128;
+    let steps = congruences.into_iter().map(|(residue, modulus)| {
+        let step = if residue >= first_step {
+            Some(residue)
+        } else {
+            ((first_step - residue + modulus - 1) / modulus).checked_mul(modulus).and_then(|x| x.checked_add(residue))
+        };
+        step.and_then(|step| u64::try_from(step).ok()).ok_or(Overflow)
+    }).collect::<Result<Vec<_>, _>>()?;
+
+    Ok(steps.into_iter().min())
+}
+
+// Find the first step where every ghost, starting from all the start nodes, is on a goal node at the same time
+fn ghost_steps(network: &Network, start: &NodeMatch, goal: &NodeMatch) -> Result<Option<u64>, Overflow> {
+    let mut is_goal = vec![false; network.names.len()];
+    for n in network.find_all(goal) {
+        is_goal[n] = true;
+    }
 
-    starting_offset as u64 + full_cycle.1[0]
+    let cycles: Vec<_> = network.find_all(start).into_iter().map(|start| {
+        Cycle::find(network, start, &is_goal)
+    }).collect();
+
+    first_common_goal(&cycles)
+}
+
+// Print facts about the network that ghost_steps relies on: which goals each start node reaches when following the
+// directions, where it enters its loop and how long the loop is, and which nodes can't be reached from any start
+fn print_report(network: &Network, start: &NodeMatch, goal: &NodeMatch) {
+    let starts = network.find_all(start);
+    let mut is_goal = vec![false; network.names.len()];
+    for n in network.find_all(goal) {
+        is_goal[n] = true;
+    }
+
+    for &start in &starts {
+        let cycle = Cycle::find(network, start, &is_goal);
+        let mut goals: Vec<_> = network.walk(start).take((cycle.start + cycle.period) as usize).filter(|&pos| {
+            is_goal[pos]
+        }).map(|pos| network.names[pos]).collect();
+        goals.sort_unstable();
+        goals.dedup();
+
+        println!(
+            "{}: enters loop after {} steps, loop length {}, goals in loop at {:?}, goals before loop at {:?}",
+            network.names[start], cycle.start, cycle.period, cycle.goal_residues, cycle.prefix_goals,
+        );
+        println!("    Reaches goals: {}", goals.join(", "));
+    }
+
+    let reachable = network.reachable(&starts);
+    let unreachable: Vec<_> = (0..network.names.len()).filter(|&n| !reachable[n]).map(|n| network.names[n]).collect();
+    println!("Unreachable from any start: {}", unreachable.join(", "));
+}
+
+fn part_1(input: &str) -> Result<u64, &'static str> {
+    let network = Network::parse(input).unwrap();
+    let (start, goal) = (NodeMatch::Exact("AAA".to_string()), NodeMatch::Exact("ZZZ".to_string()));
+    if network.find_all(&start).is_empty() {
+        return Err("no AAA start node");
+    }
+    ghost_steps(&network, &start, &goal).map_err(|_| "number of steps doesn't fit in 64 bits")?
+        .ok_or("ZZZ is never reached")
+}
+
+fn part_2(input: &str) -> Result<u64, &'static str> {
+    let network = Network::parse(input).unwrap();
+    let (start, goal) = (NodeMatch::EndsWith("A".to_string()), NodeMatch::EndsWith("Z".to_string()));
+    if network.find_all(&start).is_empty() {
+        return Err("no start nodes ending with A");
+    }
+    ghost_steps(&network, &start, &goal).map_err(|_| "number of steps doesn't fit in 64 bits")?
+        .ok_or("goals are never reached at the same time")
 }
 
 fn main() {
     let mut input = String::new();
     std::io::stdin().read_to_string(&mut input).unwrap();
 
+    let network = match Network::parse(&input) {
+        Ok(network) => network,
+        Err(err) => {
+            eprintln!("Invalid network: {}", err);
+            std::process::exit(1);
+        }
+    };
+
+    let mut args = std::env::args().skip(1);
+    let matches = |args: &mut dyn Iterator<Item = String>| {
+        let start = args.next().map_or(NodeMatch::EndsWith("A".to_string()), |arg| NodeMatch::parse(&arg));
+        let goal = args.next().map_or(NodeMatch::EndsWith("Z".to_string()), |arg| NodeMatch::parse(&arg));
+        (start, goal)
+    };
+
+    match args.next().as_deref() {
+        // Find the steps needed from other start nodes to other goal nodes
+        Some("steps") => {
+            let (start, goal) = matches(&mut args);
+            let start_time = std::time::Instant::now();
+            let result = ghost_steps(&network, &start, &goal);
+            println!("Custom time: {:?}", std::time::Instant::now() - start_time);
+            match result {
+                Ok(Some(result)) => println!("Custom result: {}", result),
+                Ok(None) => println!("Custom result: goals are never reached at the same time"),
+                Err(err) => println!("Custom result: {}", err),
+            }
+            return;
+        }
+
+        Some("report") => {
+            let (start, goal) = matches(&mut args);
+            print_report(&network, &start, &goal);
+            return;
+        }
+
+        Some("dot") => {
+            print!("{}", network.to_dot());
+            return;
+        }
+
+        Some(x) => panic!("Unknown command {}", x),
+        None => {}
+    }
+
     let start_time = std::time::Instant::now();
     let result = part_1(&input);
     println!("Part 1 time: {:?}", std::time::Instant::now() - start_time);
-    println!("Part 1 result: {}", result);
+    match result {
+        Ok(result) => println!("Part 1 result: {}", result),
+        Err(err) => println!("Part 1 result: {}", err),
+    }
 
     let start_time = std::time::Instant::now();
     let result = part_2(&input);
     println!("Part 2 time: {:?}", std::time::Instant::now() - start_time);
-    println!("Part 2 result: {}", result);
+    match result {
+        Ok(result) => println!("Part 2 result: {}", result),
+        Err(err) => println!("Part 2 result: {}", err),
+    }
 }
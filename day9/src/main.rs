@@ -1,44 +1,170 @@
 use std::io::Read;
+use num::{BigInt, Integer, rational::Ratio};
 
-fn solve(input: &str, backwards: bool) -> i32 {
-    let mut nums = Vec::new();
-    let mut differences = Vec::new();
-    let mut edge = Vec::new();
+// A polynomial in Newton form, p(x) = Σ Δ^k y_0 C(x, k), where Δ^k y_0 is the first value in the k-th row of the
+// difference table, and x is the index in the original sequence
+struct Polynomial {
+    differences: Vec<i128>,
+    // How many all-zero rows there were in the difference table, which confirm that the sequence is a polynomial
+    zero_rows: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Classification {
+    Polynomial { degree: usize },
+    // The difference table ran out of values before enough all-zero rows were found
+    InsufficientData { zero_rows: usize },
+}
+
+impl Polynomial {
+    // Build the difference table until we reach an all-zero row, keeping the first value of each row. If we run out of
+    // values first, this is the polynomial through all the values.
+    fn fit(values: &[i128]) -> Self {
+        let mut nums = values.to_vec();
+        let mut differences = Vec::new();
 
-    input.lines().map(|line| {
-        nums.clear();
-        for num in line.split_ascii_whitespace() {
-            nums.push(num.parse::<i32>().unwrap());
+        while nums.iter().any(|&x| x != 0) {
+            differences.push(nums[0]);
+            nums = nums.windows(2).map(|i| i[1] - i[0]).collect();
         }
 
-        edge.clear();
+        // Each row is one shorter than the previous, so every row after the first all-zero one is also all zeroes
+        Self { zero_rows: nums.len(), differences }
+    }
+
+    fn classify(&self, min_zero_rows: usize) -> Classification {
+        if self.zero_rows >= min_zero_rows.max(1) {
+            Classification::Polynomial { degree: self.degree() }
+        } else {
+            Classification::InsufficientData { zero_rows: self.zero_rows }
+        }
+    }
+
+    // The degree of the zero polynomial is taken to be 0
+    fn degree(&self) -> usize {
+        self.differences.len().saturating_sub(1)
+    }
+
+    // Get coefficients in the monomial basis, from the constant term up
+    fn coefficients(&self) -> Vec<Ratio<i128>> {
+        let mut coefficients = vec![Ratio::from_integer(0); self.degree() + 1];
+        // C(x, k) in the monomial basis
+        let mut binomial = vec![Ratio::from_integer(1)];
 
-        loop {
-            differences.clear();
-            for i in nums.windows(2) {
-                differences.push(if backwards { i[0] - i[1] } else { i[1] - i[0] });
+        for (k, &difference) in self.differences.iter().enumerate() {
+            for (coefficient, &b) in coefficients.iter_mut().zip(&binomial) {
+                *coefficient += b * difference;
             }
-            edge.push(nums[if backwards { 0 } else { nums.len() - 1 }]);
-            if differences.iter().all(|&x| x == 0) {
-                break edge.iter().copied().sum::<i32>();
+
+            // C(x, k + 1) = C(x, k) (x - k) / (k + 1)
+            let mut next = vec![Ratio::from_integer(0); binomial.len() + 1];
+            for (n, &b) in binomial.iter().enumerate() {
+                next[n + 1] += b;
+                next[n] -= b * k as i128;
             }
-            std::mem::swap(&mut nums, &mut differences);
+            binomial = next.into_iter().map(|b| b / (k as i128 + 1)).collect();
+        }
+
+        coefficients
+    }
+
+    // Evaluate at any index, including negative ones and ones past the end of the sequence. Each C(x, k) is an
+    // integer for integer x, so this is exact.
+    fn evaluate<T: Integer + Clone + From<i128>>(&self, x: T) -> T {
+        let mut binomial = T::one();
+        let mut sum = T::zero();
+
+        for (k, &difference) in self.differences.iter().enumerate() {
+            sum = sum + binomial.clone() * T::from(difference);
+            binomial = binomial * (x.clone() - T::from(k as i128)) / T::from(k as i128 + 1);
         }
-    }).sum()
+
+        sum
+    }
+}
+
+fn parse_sequences(input: &str) -> impl Iterator<Item = Vec<i128>> + '_ {
+    input.lines().map(|line| line.split_ascii_whitespace().map(|num| num.parse().unwrap()).collect())
 }
 
-fn part_1(input: &str) -> i32 {
-    solve(input, false)
+fn part_1(input: &str) -> i128 {
+    parse_sequences(input).map(|nums| Polynomial::fit(&nums).evaluate(nums.len() as i128)).sum()
 }
 
-fn part_2(input: &str) -> i32 {
-    solve(input, true)
+fn part_2(input: &str) -> i128 {
+    parse_sequences(input).map(|nums| Polynomial::fit(&nums).evaluate(-1)).sum()
+}
+
+// Sum of the values of every sequence at the given index, which can be arbitrarily far out
+fn sum_at(input: &str, index: &BigInt) -> BigInt {
+    parse_sequences(input).map(|nums| Polynomial::fit(&nums).evaluate(index.clone())).sum()
+}
+
+// Check that every sequence is a polynomial, confirmed by at least min_zero_rows all-zero rows, and report the ones
+// that aren't. Returns whether all sequences passed.
+fn check_sequences(input: &str, min_zero_rows: usize) -> bool {
+    let mut ok = true;
+
+    for (n, nums) in parse_sequences(input).enumerate() {
+        if let Classification::InsufficientData { zero_rows } = Polynomial::fit(&nums).classify(min_zero_rows) {
+            eprintln!(
+                "Line {}: {} values give {} all-zero difference rows, need {}",
+                n + 1, nums.len(), zero_rows, min_zero_rows.max(1),
+            );
+            ok = false;
+        }
+    }
+
+    ok
 }
 
 fn main() {
     let mut input = String::new();
     std::io::stdin().read_to_string(&mut input).unwrap();
 
+    // With --strict, sequences that aren't confirmed to be polynomials are errors rather than warnings
+    let mut args = std::env::args().skip(1).peekable();
+    let mut strict = false;
+    let mut min_zero_rows = 1;
+    while let Some(arg) = args.next_if(|arg| arg.starts_with("--")) {
+        match arg.as_str() {
+            "--strict" => { strict = true; }
+            "--min-zero-rows" => { min_zero_rows = args.next().unwrap().parse().unwrap(); }
+            _ => panic!("Unknown option {}", arg),
+        }
+    }
+
+    if !check_sequences(&input, min_zero_rows) && strict {
+        std::process::exit(1);
+    }
+
+    match args.next().as_deref() {
+        Some("fit") => {
+            for (n, nums) in parse_sequences(&input).enumerate() {
+                let polynomial = Polynomial::fit(&nums);
+                let coefficients: Vec<_> = polynomial.coefficients().iter().map(|c| c.to_string()).collect();
+                match polynomial.classify(min_zero_rows) {
+                    Classification::Polynomial { degree } => {
+                        println!("Line {}: degree {}: {}", n + 1, degree, coefficients.join(", "));
+                    }
+                    Classification::InsufficientData { .. } => {
+                        println!("Line {}: insufficient data, best fit {}", n + 1, coefficients.join(", "));
+                    }
+                }
+            }
+            return;
+        }
+
+        Some("at") => {
+            let index = args.next().unwrap().parse().unwrap();
+            println!("Sum at {}: {}", index, sum_at(&input, &index));
+            return;
+        }
+
+        Some(x) => panic!("Unknown command {}", x),
+        None => {}
+    }
+
     let start_time = std::time::Instant::now();
     let result = part_1(&input);
     println!("Part 1 time: {:?}", std::time::Instant::now() - start_time);